@@ -0,0 +1,83 @@
+use core::cmp;
+
+use crate::variable;
+
+const VARIABLE_NAME: &str = "Accessibility";
+
+/// Lowest and highest zoom steps
+pub const ZOOM_MIN: i8 = -4;
+pub const ZOOM_MAX: i8 = 12;
+/// Change of the display scale for each zoom step
+pub const ZOOM_STEP: f32 = 0.25;
+
+/// Accessibility settings, persisted in a UEFI variable
+pub struct Accessibility {
+    pub high_contrast: bool,
+    pub zoom: i8,
+}
+
+impl Accessibility {
+    /// Load the settings, using defaults if they have never been saved
+    pub fn load() -> Self {
+        let mut data = [0; 2];
+        match variable::get(VARIABLE_NAME, &mut data) {
+            Ok(2) => Self {
+                high_contrast: data[0] != 0,
+                zoom: cmp::min(cmp::max(data[1] as i8, ZOOM_MIN), ZOOM_MAX),
+            },
+            Ok(size) => {
+                debugln!("accessibility: invalid variable size {}", size);
+                Self::default()
+            },
+            Err(err) => {
+                debugln!("accessibility: failed to read variable: {:?}", err);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the settings, so they survive reboots
+    pub fn save(&self) {
+        let data = [self.high_contrast as u8, self.zoom as u8];
+        if let Err(err) = variable::set(VARIABLE_NAME, &data) {
+            debugln!("accessibility: failed to write variable: {:?}", err);
+        }
+    }
+
+    /// Change the zoom by a number of steps, returning true if it changed. Zooming in is refused
+    /// if `fits` rejects the new settings, so the content can never be zoomed off the screen
+    pub fn zoom_by<F: Fn(&Self) -> bool>(&mut self, steps: i8, fits: F) -> bool {
+        let zoom = cmp::min(cmp::max(self.zoom + steps, ZOOM_MIN), ZOOM_MAX);
+        if zoom == self.zoom {
+            return false;
+        }
+
+        let previous = self.zoom;
+        self.zoom = zoom;
+        if zoom > previous && ! fits(self) {
+            debugln!("accessibility: zoom {} does not fit", zoom);
+            self.zoom = previous;
+            return false;
+        }
+        true
+    }
+
+    /// Apply the zoom to a display scale, never going below 1
+    pub fn scale(&self, scale: f32) -> f32 {
+        let scale = scale + self.zoom as f32 * ZOOM_STEP;
+        if scale < 1.0 {
+            1.0
+        } else {
+            scale
+        }
+    }
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            zoom: 0,
+        }
+    }
+}
@@ -1,9 +1,28 @@
 use core::cell::Cell;
+use core::{cmp, mem, ptr};
 use core::ops::Try;
 use orbclient::{Color, Mode, Renderer};
 use std::proto::Protocol;
-use uefi::graphics::{GraphicsOutput, GraphicsBltOp, GraphicsBltPixel};
+use uefi::graphics::{GraphicsOutput, GraphicsOutputModeInfo, GraphicsBltOp, GraphicsBltPixel};
 use uefi::guid::{Guid, GRAPHICS_OUTPUT_PROTOCOL_GUID};
+use uefi::status::Result;
+
+use crate::edid::Edid;
+use crate::variable;
+
+/// DPI at which the UI is drawn without scaling
+const REFERENCE_DPI: f32 = 96.0;
+/// Largest scale chosen automatically
+const SCALE_MAX: f32 = 8.0;
+/// Variable with the rotation of every output in degrees
+const ROTATION_VARIABLE: &str = "Rotation";
+
+// TODO: move to uefi library {
+pub const PIXEL_RED_GREEN_BLUE_RESERVED_8BIT: u32 = 0;
+pub const PIXEL_BLUE_GREEN_RED_RESERVED_8BIT: u32 = 1;
+
+pub const DEVICE_PATH_PROTOCOL_GUID: Guid = Guid(0x09576e91, 0x6d3f, 0x11d2, [0x8e, 0x39, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b]);
+// } TODO: move to uefi library
 
 pub struct Output(pub &'static mut GraphicsOutput);
 
@@ -17,41 +36,358 @@ impl Protocol<GraphicsOutput> for Output {
     }
 }
 
+impl Output {
+    /// Every graphics output of a physical device. The console splitter installs a virtual
+    /// output on the console handle that already mirrors to the others, and setting its mode
+    /// changes every output, so it is skipped along with anything else without a device path
+    pub fn physical() -> Vec<Output> {
+        let uefi = std::system_table();
+
+        let handles = match Output::locate_handle() {
+            Ok(ok) => ok,
+            Err(err) => {
+                debugln!("failed to locate graphics outputs: {:?}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut outputs = Vec::new();
+        for handle in handles {
+            if handle == uefi.ConsoleOutHandle {
+                continue;
+            }
+
+            let mut interface = 0;
+            let status = (uefi.BootServices.HandleProtocol)(handle, &DEVICE_PATH_PROTOCOL_GUID, &mut interface);
+            if status.into_result().is_err() {
+                continue;
+            }
+
+            if let Ok(output) = Output::handle_protocol(handle) {
+                outputs.push(output);
+            }
+        }
+
+        // Use whatever there is if no output has a device path
+        if outputs.is_empty() {
+            Output::all()
+        } else {
+            outputs
+        }
+    }
+
+    /// Every mode of the output, as the mode number, width and height
+    pub fn modes(&mut self) -> Vec<(u32, u32, u32)> {
+        let uefi = std::system_table();
+
+        let mut modes = Vec::new();
+        for mode in 0..self.0.Mode.MaxMode {
+            let mut size = 0;
+            let mut info_ptr: *mut GraphicsOutputModeInfo = ptr::null_mut();
+            match (self.0.QueryMode)(self.0, mode, &mut size, &mut info_ptr).into_result() {
+                Ok(_) => if ! info_ptr.is_null() {
+                    let info = unsafe { &*info_ptr };
+                    modes.push((mode, info.HorizontalResolution, info.VerticalResolution));
+                    let _ = (uefi.BootServices.FreePool)(info_ptr as usize);
+                },
+                Err(err) => {
+                    debugln!("failed to query mode {}: {:?}", mode, err);
+                }
+            }
+        }
+        modes
+    }
+
+    /// Copy a region of a buffer the size of the output to the same place on the output.
+    /// Linear framebuffers with a known pixel format are written directly, anything else, like
+    /// BltOnly modes, goes through Blt
+    fn write(&mut self, buffer: &mut [Color], x: usize, y: usize, w: usize, h: usize) -> bool {
+        let mode = &self.0.Mode;
+        let stride = mode.Info.HorizontalResolution as usize;
+        let format = mode.Info.PixelFormat;
+        let fb_stride = mode.Info.PixelsPerScanLine as usize;
+        let fb_len = mode.FrameBufferSize / mem::size_of::<u32>();
+        let direct = mode.FrameBufferBase != 0
+            && (format == PIXEL_BLUE_GREEN_RED_RESERVED_8BIT || format == PIXEL_RED_GREEN_BLUE_RESERVED_8BIT)
+            && fb_stride >= stride
+            && (y + h) * fb_stride <= fb_len;
+
+        if direct {
+            let fb = mode.FrameBufferBase as *mut u32;
+            for row in y..y + h {
+                let src = &buffer[row * stride + x..row * stride + x + w];
+                unsafe {
+                    let dst = fb.add(row * fb_stride + x);
+                    if format == PIXEL_BLUE_GREEN_RED_RESERVED_8BIT {
+                        // Colors are already stored as blue, green, red, reserved
+                        fast_copy(dst as *mut u8, src.as_ptr() as *const u8, w * mem::size_of::<u32>());
+                    } else {
+                        for (i, color) in src.iter().enumerate() {
+                            let data = color.data;
+                            let swapped = (data & 0xFF00FF00) | ((data >> 16) & 0xFF) | ((data & 0xFF) << 16);
+                            ptr::write_volatile(dst.add(i), swapped);
+                        }
+                    }
+                }
+            }
+            return true;
+        }
+
+        let status = (self.0.Blt)(
+            self.0,
+            buffer.as_mut_ptr() as *mut GraphicsBltPixel,
+            GraphicsBltOp::BufferToVideo,
+            x,
+            y,
+            x,
+            y,
+            w,
+            h,
+            stride * mem::size_of::<Color>()
+        );
+        status.into_result().is_ok()
+    }
+}
+
+/// Clockwise rotation of the picture on the output, for panels that are not mounted in the
+/// orientation of their native mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    /// The configured rotation, which is 0, 90, 180 or 270 degrees
+    pub fn configured() -> Self {
+        let mut data = [0; 2];
+        match variable::get(ROTATION_VARIABLE, &mut data) {
+            Ok(2) => match data[0] as u16 | (data[1] as u16) << 8 {
+                0 => Rotation::None,
+                90 => Rotation::Clockwise90,
+                180 => Rotation::Clockwise180,
+                270 => Rotation::Clockwise270,
+                degrees => {
+                    debugln!("rotation: unsupported rotation of {} degrees", degrees);
+                    Rotation::None
+                }
+            },
+            _ => Rotation::None,
+        }
+    }
+
+    /// True if the width and height are swapped
+    fn transposed(&self) -> bool {
+        *self == Rotation::Clockwise90 || *self == Rotation::Clockwise270
+    }
+
+    /// Turn a position on the output, given as offsets of x out of w and y out of h, into the
+    /// same kind of position on the rotated display
+    pub fn to_display(&self, x: u64, y: u64, w: u64, h: u64) -> (u64, u64, u64, u64) {
+        match self {
+            Rotation::None => (x, y, w, h),
+            Rotation::Clockwise90 => (y, w - x, h, w),
+            Rotation::Clockwise180 => (w - x, h - y, w, h),
+            Rotation::Clockwise270 => (h - y, x, h, w),
+        }
+    }
+}
+
 pub struct Display {
     output: Output,
+    rotation: Rotation,
+    // Size that is drawn to, which is the size of the output with the rotation applied
     w: u32,
     h: u32,
     data: Box<[Color]>,
+    // Copy of what was last blitted, used to find damaged regions
+    shadow: Option<Box<[Color]>>,
+    // Rotated copy of data in the orientation of the output, only used with a rotation
+    rotated: Box<[Color]>,
+    scale: f32,
     mode: Cell<Mode>,
 }
 
 impl Display {
     pub fn new(output: Output) -> Self {
-        let w = output.0.Mode.Info.HorizontalResolution;
-        let h = output.0.Mode.Info.VerticalResolution;
-        Self {
+        let mut display = Self {
             output: output,
-            w: w,
-            h: h,
-            data: vec![Color::rgb(0, 0, 0); w as usize * h as usize].into_boxed_slice(),
+            rotation: Rotation::configured(),
+            w: 0,
+            h: 0,
+            data: Box::new([]),
+            shadow: None,
+            rotated: Box::new([]),
+            scale: 1.0,
             mode: Cell::new(Mode::Blend),
-        }
+        };
+        display.resize();
+        display
+    }
+
+    /// Rebuild the buffers to match the mode of the output
+    fn resize(&mut self) {
+        let output_w = self.output.0.Mode.Info.HorizontalResolution;
+        let output_h = self.output.0.Mode.Info.VerticalResolution;
+        let (w, h) = if self.rotation.transposed() {
+            (output_h, output_w)
+        } else {
+            (output_w, output_h)
+        };
+
+        self.w = w;
+        self.h = h;
+        self.data = vec![Color::rgb(0, 0, 0); w as usize * h as usize].into_boxed_slice();
+        self.shadow = None;
+        self.rotated = if self.rotation == Rotation::None {
+            Box::new([])
+        } else {
+            vec![Color::rgb(0, 0, 0); w as usize * h as usize].into_boxed_slice()
+        };
+        self.scale = output_scale(&self.output, output_w, output_h);
+    }
+
+
+    /// Every mode of the output, as the mode number, width and height
+    pub fn modes(&mut self) -> Vec<(u32, u32, u32)> {
+        self.output.modes()
+    }
+
+    /// The current mode number of the output
+    pub fn output_mode(&self) -> u32 {
+        self.output.0.Mode.Mode
+    }
+
+    /// The EDID of the output, if it has one
+    pub fn edid(&self) -> Option<Edid> {
+        Edid::from_output(&self.output)
+    }
+
+    /// Switch the output to another mode, rebuilding the buffer at the new resolution
+    pub fn set_output_mode(&mut self, mode: u32) -> Result<()> {
+        (self.output.0.SetMode)(self.output.0, mode)?;
+        self.resize();
+        Ok(())
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Scale of the UI on this display, shared by the text console and the form display
+    pub fn scale(&self) -> f32 {
+        self.scale
     }
 
     pub fn blit(&mut self, x: i32, y: i32, w: u32, h: u32) -> bool {
-        let status = (self.output.0.Blt)(
-            self.output.0,
-            self.data.as_mut_ptr() as *mut GraphicsBltPixel,
-            GraphicsBltOp::BufferToVideo,
-            x as usize,
-            y as usize,
-            x as usize,
-            y as usize,
-            w as usize,
-            h as usize,
-            0
-        );
-        status.into_result().is_ok()
+        if self.rotation != Rotation::None {
+            return self.blit_rotated(x, y, w, h);
+        }
+
+        let x1 = cmp::max(x, 0);
+        let y1 = cmp::max(y, 0);
+        let x2 = cmp::min(x + w as i32, self.w as i32);
+        let y2 = cmp::min(y + h as i32, self.h as i32);
+        if x1 >= x2 || y1 >= y2 {
+            return true;
+        }
+
+        self.output.write(
+            &mut self.data,
+            x1 as usize,
+            y1 as usize,
+            (x2 - x1) as usize,
+            (y2 - y1) as usize
+        )
+    }
+
+    /// Rotate a region into the orientation of the output, and blit it from there
+    fn blit_rotated(&mut self, x: i32, y: i32, w: u32, h: u32) -> bool {
+        let (dw, dh) = (self.w as i32, self.h as i32);
+        let x1 = cmp::max(x, 0);
+        let y1 = cmp::max(y, 0);
+        let x2 = cmp::min(x + w as i32, dw);
+        let y2 = cmp::min(y + h as i32, dh);
+        if x1 >= x2 || y1 >= y2 {
+            return true;
+        }
+
+        // Width of the output, and the region on the output
+        let (output_w, (ox, oy, ow, oh)) = match self.rotation {
+            Rotation::None => (dw, (x1, y1, x2 - x1, y2 - y1)),
+            Rotation::Clockwise90 => (dh, (dh - y2, x1, y2 - y1, x2 - x1)),
+            Rotation::Clockwise180 => (dw, (dw - x2, dh - y2, x2 - x1, y2 - y1)),
+            Rotation::Clockwise270 => (dh, (y1, dw - x2, y2 - y1, x2 - x1)),
+        };
+
+        for row in y1..y2 {
+            for col in x1..x2 {
+                let (px, py) = match self.rotation {
+                    Rotation::None => (col, row),
+                    Rotation::Clockwise90 => (dh - 1 - row, col),
+                    Rotation::Clockwise180 => (dw - 1 - col, dh - 1 - row),
+                    Rotation::Clockwise270 => (row, dw - 1 - col),
+                };
+                self.rotated[(py * output_w + px) as usize] = self.data[(row * dw + col) as usize];
+            }
+        }
+
+        self.output.write(&mut self.rotated, ox as usize, oy as usize, ow as usize, oh as usize)
+    }
+
+    /// Forget what is on screen, so that the next sync_damage blits everything
+    pub fn invalidate(&mut self) {
+        self.shadow = None;
+    }
+
+    /// Blit only the regions that changed since the last sync, one band of changed rows at a time
+    pub fn sync_damage(&mut self) -> bool {
+        let w = self.w as usize;
+        let h = self.h as usize;
+
+        let mut shadow = match self.shadow.take() {
+            Some(shadow) => shadow,
+            None => return self.sync(),
+        };
+
+        let mut ok = true;
+        let mut y = 0;
+        while y < h {
+            // Find a band of rows that changed, and the columns that changed within it
+            let mut x1 = w;
+            let mut x2 = 0;
+            let mut y2 = y;
+            while y2 < h {
+                let row = &self.data[y2 * w..(y2 + 1) * w];
+                let shadow_row = &shadow[y2 * w..(y2 + 1) * w];
+                match row.iter().zip(shadow_row.iter()).position(|(a, b)| a.data != b.data) {
+                    Some(first) => {
+                        let last = w - 1 - row.iter().rev().zip(shadow_row.iter().rev()).position(|(a, b)| a.data != b.data).unwrap_or(0);
+                        if first < x1 {
+                            x1 = first;
+                        }
+                        if last + 1 > x2 {
+                            x2 = last + 1;
+                        }
+                        y2 += 1;
+                    },
+                    None => break,
+                }
+            }
+
+            if y2 > y {
+                ok &= self.blit(x1 as i32, y as i32, (x2 - x1) as u32, (y2 - y) as u32);
+                shadow[y * w..y2 * w].copy_from_slice(&self.data[y * w..y2 * w]);
+                y = y2;
+            } else {
+                y += 1;
+            }
+        }
+
+        self.shadow = Some(shadow);
+        ok
     }
 
     pub fn scroll(&mut self, rows: usize, color: Color) {
@@ -89,6 +425,14 @@ impl Renderer for Display {
     fn sync(&mut self) -> bool {
         let w = self.width();
         let h = self.height();
+        let shadow = match self.shadow.take() {
+            Some(mut shadow) => {
+                shadow.copy_from_slice(&self.data);
+                shadow
+            },
+            None => self.data.clone(),
+        };
+        self.shadow = Some(shadow);
         self.blit(0, 0, w, h)
     }
 
@@ -97,87 +441,156 @@ impl Renderer for Display {
     }
 }
 
+/// Choose the UI scale of an output. The DPI is calculated from the physical size in the EDID,
+/// rounded to a quarter step. Without it, the scale is guessed from the vertical resolution
+fn output_scale(output: &Output, w: u32, h: u32) -> f32 {
+    let size_opt = Edid::from_output(output).and_then(|edid| edid.physical_size());
+    if let Some((w_mm, h_mm)) = size_opt {
+        // The smaller DPI is used, in case one dimension is rounded up
+        let dpi_x = w as f32 * 25.4 / w_mm as f32;
+        let dpi_y = h as f32 * 25.4 / h_mm as f32;
+        let dpi = if dpi_x < dpi_y { dpi_x } else { dpi_y };
+        debugln!("display: {}x{} at {}x{} mm, {} DPI", w, h, w_mm, h_mm, dpi);
+
+        // Sizes of TVs and projectors are not always meaningful
+        if dpi >= REFERENCE_DPI / 2.0 && dpi <= REFERENCE_DPI * SCALE_MAX {
+            let scale = ((dpi / REFERENCE_DPI) * 4.0 + 0.5) as i32 as f32 / 4.0;
+            return if scale < 1.0 { 1.0 } else { scale };
+        }
+    }
+
+    if h > 1440 {
+        4.0
+    } else if h > 720 {
+        2.0
+    } else {
+        1.0
+    }
+}
+
+/// Apply a scale to a size, rounding to the nearest pixel
+pub fn scaled(size: i32, scale: f32) -> i32 {
+    let scaled = size as f32 * scale;
+    if scaled < 0.0 {
+        (scaled - 0.5) as i32
+    } else {
+        (scaled + 0.5) as i32
+    }
+}
+
+/// Renderer at a lower resolution that is scaled up to a display when blitting. Everything is
+/// drawn to a buffer of its own size, so every orbclient primitive works at any scale
 pub struct ScaledDisplay<'a> {
     display: &'a mut Display,
-    scale: u32,
+    scale: f32,
+    w: u32,
+    h: u32,
+    data: Box<[Color]>,
 }
 
 impl<'a> ScaledDisplay<'a> {
     pub fn new(display: &'a mut Display) -> Self {
-        let scale = if display.height() > 1440 {
-            2
-        } else {
-            1
-        };
+        let scale = display.scale();
+        let w = (display.width() as f32 / scale) as u32;
+        let h = (display.height() as f32 / scale) as u32;
 
         Self {
             display,
             scale,
+            w,
+            h,
+            data: vec![Color::rgb(0, 0, 0); w as usize * h as usize].into_boxed_slice(),
         }
     }
 
-    pub fn scale(&self) -> u32 {
+    pub fn scale(&self) -> f32 {
         self.scale
     }
 
     pub fn scroll(&mut self, rows: usize, color: Color) {
-        let scale = self.scale as usize;
-        self.display.scroll(rows * scale, color);
+        let width = self.w as usize;
+        let height = self.h as usize;
+        if rows > 0 && rows < height {
+            let off = rows * width;
+            self.data.copy_within(off.., 0);
+            let len = self.data.len();
+            for pixel in self.data[len - off..].iter_mut() {
+                *pixel = color;
+            }
+        }
     }
 
-    pub fn blit(&mut self, x: i32, y: i32, w: u32, h: u32) -> bool {
+    /// Scale a region up into the display, each display pixel takes the nearest pixel here.
+    /// Returns the region of the display that was written
+    fn scale_up(&mut self, x: i32, y: i32, w: u32, h: u32) -> (i32, i32, i32, i32) {
+        let (sw, sh) = (self.w as i32, self.h as i32);
+        let x1 = scaled(cmp::max(x, 0), self.scale);
+        let y1 = scaled(cmp::max(y, 0), self.scale);
+        let x2 = cmp::min(scaled(cmp::min(x + w as i32, sw), self.scale), self.display.width() as i32);
+        let y2 = cmp::min(scaled(cmp::min(y + h as i32, sh), self.scale), self.display.height() as i32);
+        if x1 >= x2 || y1 >= y2 {
+            return (0, 0, 0, 0);
+        }
+
+        // The center of each display pixel is used, so every region maps a pixel the same way
         let scale = self.scale;
-        self.display.blit(
-            x * scale as i32,
-            y * scale as i32,
-            w * scale,
-            h * scale
-        )
+        let nearest = |i: i32, max: i32| cmp::min(((i as f32 + 0.5) / scale) as i32, max - 1);
+
+        let stride = self.display.width() as usize;
+        let row_w = (x2 - x1) as usize;
+        let display_data = self.display.data_mut();
+        let mut last_opt: Option<(i32, usize)> = None;
+        for row in y1..y2 {
+            let offset = row as usize * stride + x1 as usize;
+            let src_row = nearest(row, sh);
+            match last_opt {
+                // Rows scaled from the same row are the same
+                Some((last_row, last_offset)) if last_row == src_row => {
+                    display_data.copy_within(last_offset..last_offset + row_w, offset);
+                },
+                _ => {
+                    let src = &self.data[src_row as usize * sw as usize..(src_row as usize + 1) * sw as usize];
+                    for (i, pixel) in display_data[offset..offset + row_w].iter_mut().enumerate() {
+                        *pixel = src[nearest(x1 + i as i32, sw) as usize];
+                    }
+                }
+            }
+            last_opt = Some((src_row, offset));
+        }
+
+        (x1, y1, x2 - x1, y2 - y1)
+    }
+
+    pub fn blit(&mut self, x: i32, y: i32, w: u32, h: u32) -> bool {
+        let (x, y, w, h) = self.scale_up(x, y, w, h);
+        self.display.blit(x, y, w as u32, h as u32)
     }
 }
 
 impl<'a> Renderer for ScaledDisplay<'a> {
     fn width(&self) -> u32 {
-        self.display.width()/self.scale
+        self.w
     }
 
     fn height(&self) -> u32 {
-        self.display.height()/self.scale
+        self.h
     }
 
     fn data(&self) -> &[Color] {
-        unreachable!()
+        &self.data
     }
 
     fn data_mut(&mut self) -> &mut [Color] {
-        unreachable!()
+        &mut self.data
     }
 
     fn sync(&mut self) -> bool {
+        let w = self.w;
+        let h = self.h;
+        self.scale_up(0, 0, w, h);
         self.display.sync()
     }
 
-    fn pixel(&mut self, x: i32, y: i32, color: Color) {
-        self.rect(x, y, 1, 1, color);
-    }
-
-    fn rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
-        let scale = self.scale;
-        self.display.rect(
-            x * scale as i32,
-            y * scale as i32,
-            w * scale,
-            h * scale,
-            color
-        );
-    }
-
-    fn set(&mut self, color: Color) {
-        let w = self.width();
-        let h = self.height();
-        self.rect(0, 0, w, h, color);
-    }
-
     fn mode(&self) -> &Cell<Mode> {
         self.display.mode()
     }
@@ -0,0 +1,134 @@
+use core::slice;
+use std::proto::Protocol;
+use uefi::guid::Guid;
+
+use crate::display::Output;
+
+// TODO: move to uefi library {
+pub const EDID_ACTIVE_GUID: Guid = Guid(0xbd8c1056, 0x9f36, 0x44ec, [0x92, 0xa8, 0xa6, 0x33, 0x7f, 0x81, 0x79, 0x86]);
+pub const EDID_DISCOVERED_GUID: Guid = Guid(0x1c0c34f6, 0xd380, 0x41fa, [0xa0, 0x49, 0x8a, 0xd6, 0x6a, 0xa1, 0xa1, 0xaa]);
+
+/// Layout shared by the EDID Active and EDID Discovered protocols
+#[repr(C)]
+pub struct EdidProtocol {
+    pub SizeOfEdid: u32,
+    pub Edid: *const u8,
+}
+
+pub struct EdidActive(pub &'static mut EdidProtocol);
+
+impl Protocol<EdidProtocol> for EdidActive {
+    fn guid() -> Guid {
+        EDID_ACTIVE_GUID
+    }
+
+    fn new(inner: &'static mut EdidProtocol) -> Self {
+        EdidActive(inner)
+    }
+}
+
+pub struct EdidDiscovered(pub &'static mut EdidProtocol);
+
+impl Protocol<EdidProtocol> for EdidDiscovered {
+    fn guid() -> Guid {
+        EDID_DISCOVERED_GUID
+    }
+
+    fn new(inner: &'static mut EdidProtocol) -> Self {
+        EdidDiscovered(inner)
+    }
+}
+// } TODO: move to uefi library
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const EDID_BLOCK_SIZE: usize = 128;
+
+/// Base EDID block of a display
+pub struct Edid {
+    data: Vec<u8>,
+}
+
+impl Edid {
+    /// Parse an EDID, checking the header and checksum of the base block
+    pub fn new(data: &[u8]) -> Option<Self> {
+        if data.len() < EDID_BLOCK_SIZE || data[..8] != EDID_HEADER {
+            return None;
+        }
+
+        let sum = data[..EDID_BLOCK_SIZE].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if sum != 0 {
+            debugln!("edid: invalid checksum {:#x}", sum);
+            return None;
+        }
+
+        Some(Self {
+            data: data[..EDID_BLOCK_SIZE].to_vec(),
+        })
+    }
+
+    /// Read the EDID of a graphics output, preferring the active EDID over the discovered one
+    pub fn from_output(output: &Output) -> Option<Self> {
+        let output_ptr = output.0 as *const _;
+        let handles = Output::locate_handle().ok()?;
+        let handle = handles.into_iter().find(|&handle| {
+            Output::handle_protocol(handle).map_or(false, |other| other.0 as *const _ == output_ptr)
+        })?;
+
+        let protocol = EdidActive::handle_protocol(handle).map(|edid| edid.0)
+            .or_else(|_| EdidDiscovered::handle_protocol(handle).map(|edid| edid.0))
+            .ok()?;
+
+        if protocol.Edid.is_null() || protocol.SizeOfEdid == 0 {
+            return None;
+        }
+
+        let data = unsafe {
+            slice::from_raw_parts(protocol.Edid, protocol.SizeOfEdid as usize)
+        };
+        Self::new(data)
+    }
+
+    /// The first detailed timing descriptor, which is the preferred timing
+    fn preferred_timing(&self) -> Option<&[u8]> {
+        let descriptor = &self.data[54..72];
+        // Other descriptors have a pixel clock of zero
+        if descriptor[0] == 0 && descriptor[1] == 0 {
+            None
+        } else {
+            Some(descriptor)
+        }
+    }
+
+    /// The preferred, normally native, resolution in pixels
+    pub fn preferred_resolution(&self) -> Option<(u32, u32)> {
+        let timing = self.preferred_timing()?;
+        let w = timing[2] as u32 | ((timing[4] as u32 & 0xF0) << 4);
+        let h = timing[5] as u32 | ((timing[7] as u32 & 0xF0) << 4);
+        if w > 0 && h > 0 {
+            Some((w, h))
+        } else {
+            None
+        }
+    }
+
+    /// The physical size of the image in millimeters
+    pub fn physical_size(&self) -> Option<(u32, u32)> {
+        // The preferred timing is in millimeters, the basic parameters only in centimeters
+        if let Some(timing) = self.preferred_timing() {
+            let w = timing[12] as u32 | ((timing[14] as u32 & 0xF0) << 4);
+            let h = timing[13] as u32 | ((timing[14] as u32 & 0x0F) << 8);
+            if w > 0 && h > 0 {
+                return Some((w, h));
+            }
+        }
+
+        // Both are zero for projectors, and one is an aspect ratio for some displays
+        let w = self.data[21] as u32 * 10;
+        let h = self.data[22] as u32 * 10;
+        if w > 0 && h > 0 {
+            Some((w, h))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,524 @@
+use core::{cmp, ptr};
+
+use crate::key::Key;
+
+/// Overwrite data with zeros in a way that is not optimized out, so that secrets such as
+/// passwords do not stay in memory once it is freed
+pub fn zero<T: Copy + Default>(data: &mut [T]) {
+    for item in data.iter_mut() {
+        unsafe { ptr::write_volatile(item, T::default()) };
+    }
+}
+
+/// Overwrite a string with zeros and empty it
+pub fn zero_string(string: &mut String) {
+    // Zero bytes are valid UTF-8
+    zero(unsafe { string.as_bytes_mut() });
+    string.clear();
+}
+
+/// Single line text editor, used for string and password questions
+pub struct LineEditor {
+    chars: Vec<char>,
+    cursor: usize,
+    min: usize,
+    max: usize,
+}
+
+impl LineEditor {
+    /// Create a new editor, with the cursor placed after the initial string
+    pub fn new(string: &str, min: usize, max: usize) -> Self {
+        // Reserved up front, so typing never leaves a copy behind in a reallocated buffer
+        let mut chars = Vec::with_capacity(max);
+        chars.extend(string.chars().take(max));
+        let cursor = chars.len();
+        Self {
+            chars,
+            cursor,
+            min,
+            max,
+        }
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    pub fn string(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn min(&self) -> usize {
+        self.min
+    }
+
+    /// Returns true if the text satisfies the minimum length
+    pub fn valid(&self) -> bool {
+        self.chars.len() >= self.min
+    }
+
+    /// Empty the editor, zeroing what was typed
+    pub fn clear(&mut self) {
+        // Removed characters may be left past the end, so the whole capacity is zeroed
+        let capacity = self.chars.capacity();
+        self.chars.resize(capacity, '\0');
+        zero(&mut self.chars);
+        self.chars.clear();
+        self.cursor = 0;
+    }
+
+    /// Handle a key press, returning false if the key is not used by the editor
+    pub fn key(&mut self, key: &Key) -> bool {
+        match *key {
+            Key::Left => if self.cursor > 0 {
+                self.cursor -= 1;
+            },
+            Key::Right => if self.cursor < self.chars.len() {
+                self.cursor += 1;
+            },
+            Key::Home => {
+                self.cursor = 0;
+            },
+            Key::End => {
+                self.cursor = self.chars.len();
+            },
+            Key::Backspace => if self.cursor > 0 {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            },
+            Key::Delete => if self.cursor < self.chars.len() {
+                self.chars.remove(self.cursor);
+            },
+            Key::Character(c) => if ! c.is_control() && self.chars.len() < self.max {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Drop for LineEditor {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericDisplay {
+    Signed,
+    Unsigned,
+    Hex,
+}
+
+/// Storage size, display format, and limits of a numeric question
+#[derive(Clone, Copy, Debug)]
+pub struct Numeric {
+    pub size: u32,
+    pub display: NumericDisplay,
+    pub min: u64,
+    pub max: u64,
+    pub step: u64,
+}
+
+impl Numeric {
+    fn mask(&self) -> u64 {
+        if self.size >= 8 {
+            !0
+        } else {
+            (1 << (self.size * 8)) - 1
+        }
+    }
+
+    /// Sign extend a value of this size
+    fn signed(&self, value: u64) -> i64 {
+        let shift = 64 - cmp::min(self.size, 8) * 8;
+        ((value << shift) as i64) >> shift
+    }
+
+    fn less(&self, a: u64, b: u64) -> bool {
+        match self.display {
+            NumericDisplay::Signed => self.signed(a) < self.signed(b),
+            _ => (a & self.mask()) < (b & self.mask()),
+        }
+    }
+
+    pub fn in_range(&self, value: u64) -> bool {
+        ! self.less(value, self.min) && ! self.less(self.max, value)
+    }
+
+    pub fn clamp(&self, value: u64) -> u64 {
+        if self.less(value, self.min) {
+            self.min
+        } else if self.less(self.max, value) {
+            self.max
+        } else {
+            value
+        }
+    }
+
+    pub fn increment(&self, value: u64) -> u64 {
+        let step = cmp::max(self.step, 1);
+        match self.display {
+            NumericDisplay::Signed => {
+                let next = self.signed(value).saturating_add(step as i64);
+                self.clamp(cmp::min(next, self.signed(self.max)) as u64 & self.mask())
+            },
+            _ => {
+                let next = (value & self.mask()).saturating_add(step);
+                self.clamp(cmp::min(next, self.max & self.mask()))
+            },
+        }
+    }
+
+    pub fn decrement(&self, value: u64) -> u64 {
+        let step = cmp::max(self.step, 1);
+        match self.display {
+            NumericDisplay::Signed => {
+                let next = self.signed(value).saturating_sub(step as i64);
+                self.clamp(cmp::max(next, self.signed(self.min)) as u64 & self.mask())
+            },
+            _ => {
+                let next = (value & self.mask()).saturating_sub(step);
+                self.clamp(cmp::max(next, self.min & self.mask()))
+            },
+        }
+    }
+
+    pub fn format(&self, value: u64) -> String {
+        match self.display {
+            NumericDisplay::Signed => format!("{}", self.signed(value)),
+            NumericDisplay::Unsigned => format!("{}", value & self.mask()),
+            NumericDisplay::Hex => format!("0x{:X}", value & self.mask()),
+        }
+    }
+
+    /// Parse a typed value, which must fit in the storage size
+    pub fn parse(&self, string: &str) -> Option<u64> {
+        match self.display {
+            NumericDisplay::Signed => {
+                let value = string.parse::<i64>().ok()?;
+                if self.signed(value as u64 & self.mask()) == value {
+                    Some(value as u64 & self.mask())
+                } else {
+                    None
+                }
+            },
+            NumericDisplay::Unsigned | NumericDisplay::Hex => {
+                let radix = if self.display == NumericDisplay::Hex { 16 } else { 10 };
+                let value = u64::from_str_radix(string, radix).ok()?;
+                if value & self.mask() == value {
+                    Some(value)
+                } else {
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn range_error(&self) -> String {
+        format!("Value must be between {} and {}", self.format(self.min), self.format(self.max))
+    }
+}
+
+/// Editor for numeric questions, supporting stepping and typed entry
+pub struct NumericEditor {
+    numeric: Numeric,
+    value: u64,
+    typed: String,
+}
+
+impl NumericEditor {
+    pub fn new(numeric: Numeric, value: u64) -> Self {
+        Self {
+            numeric,
+            value,
+            typed: String::new(),
+        }
+    }
+
+    /// The string shown while editing, which is the typed text if any
+    pub fn string(&self) -> String {
+        if self.typed.is_empty() {
+            self.numeric.format(self.value)
+        } else if self.numeric.display == NumericDisplay::Hex {
+            format!("0x{}", self.typed)
+        } else {
+            self.typed.clone()
+        }
+    }
+
+    /// The value to be submitted, or an error describing why it is invalid
+    pub fn value(&self) -> Result<u64, String> {
+        if self.typed.is_empty() || self.typed == "-" {
+            return Ok(self.value);
+        }
+
+        match self.numeric.parse(&self.typed) {
+            Some(value) => if self.numeric.in_range(value) {
+                Ok(value)
+            } else {
+                Err(self.numeric.range_error())
+            },
+            None => Err(self.numeric.range_error()),
+        }
+    }
+
+    /// Handle a key press, returning false if the key is not used by the editor
+    pub fn key(&mut self, key: &Key) -> bool {
+        match *key {
+            Key::Up | Key::Character('+') => {
+                if let Ok(value) = self.value() {
+                    self.value = self.numeric.increment(value);
+                }
+                self.typed.clear();
+            },
+            Key::Down => {
+                if let Ok(value) = self.value() {
+                    self.value = self.numeric.decrement(value);
+                }
+                self.typed.clear();
+            },
+            Key::Character('-') => {
+                if self.numeric.display == NumericDisplay::Signed && self.typed.is_empty() {
+                    // Start typing a negative number
+                    self.typed.push('-');
+                } else {
+                    if let Ok(value) = self.value() {
+                        self.value = self.numeric.decrement(value);
+                    }
+                    self.typed.clear();
+                }
+            },
+            Key::Backspace => {
+                self.typed.pop();
+            },
+            Key::Character(c) => {
+                let radix = if self.numeric.display == NumericDisplay::Hex { 16 } else { 10 };
+                if c.is_digit(radix) && self.typed.len() < 20 {
+                    self.typed.push(c.to_ascii_uppercase());
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateTimeKind {
+    Date,
+    Time,
+}
+
+/// Segmented editor for date and time questions
+///
+/// Dates are stored in display order, month/day/year, and times as hour:minute:second
+pub struct DateTimeEditor {
+    kind: DateTimeKind,
+    fields: [u16; 3],
+    visible: [bool; 3],
+    segment: usize,
+    typed: String,
+}
+
+impl DateTimeEditor {
+    pub fn new(kind: DateTimeKind, fields: [u16; 3], visible: [bool; 3]) -> Self {
+        let segment = visible.iter().position(|&x| x).unwrap_or(0);
+        let mut editor = Self {
+            kind,
+            fields,
+            visible,
+            segment,
+            typed: String::new(),
+        };
+        for i in 0..3 {
+            editor.fields[i] = editor.clamp(i, editor.fields[i]);
+        }
+        editor
+    }
+
+    pub fn kind(&self) -> DateTimeKind {
+        self.kind
+    }
+
+    fn name(&self, segment: usize) -> &'static str {
+        match (self.kind, segment) {
+            (DateTimeKind::Date, 0) => "Month",
+            (DateTimeKind::Date, 1) => "Day",
+            (DateTimeKind::Date, _) => "Year",
+            (DateTimeKind::Time, 0) => "Hour",
+            (DateTimeKind::Time, 1) => "Minute",
+            (DateTimeKind::Time, _) => "Second",
+        }
+    }
+
+    fn width(&self, segment: usize) -> usize {
+        match (self.kind, segment) {
+            (DateTimeKind::Date, 2) => 4,
+            _ => 2,
+        }
+    }
+
+    fn range(&self, segment: usize) -> (u16, u16) {
+        match (self.kind, segment) {
+            (DateTimeKind::Date, 0) => (1, 12),
+            (DateTimeKind::Date, 1) => (1, days_in_month(self.fields[2], self.fields[0])),
+            (DateTimeKind::Date, _) => (1900, 9999),
+            (DateTimeKind::Time, 0) => (0, 23),
+            (DateTimeKind::Time, _) => (0, 59),
+        }
+    }
+
+    fn clamp(&self, segment: usize, value: u16) -> u16 {
+        let (min, max) = self.range(segment);
+        cmp::max(min, cmp::min(max, value))
+    }
+
+    fn set(&mut self, segment: usize, value: u16) {
+        self.fields[segment] = value;
+        // Changing the month or year may reduce the days in the month
+        if self.kind == DateTimeKind::Date && segment != 1 {
+            self.fields[1] = self.clamp(1, self.fields[1]);
+        }
+    }
+
+    fn move_segment(&mut self, forward: bool) {
+        let mut segment = self.segment;
+        for _ in 0..3 {
+            segment = if forward { (segment + 1) % 3 } else { (segment + 2) % 3 };
+            if self.visible[segment] {
+                break;
+            }
+        }
+        self.segment = segment;
+    }
+
+    /// Commit typed digits to the current segment
+    fn commit(&mut self) -> Result<(), String> {
+        if self.typed.is_empty() {
+            return Ok(());
+        }
+
+        let (min, max) = self.range(self.segment);
+        match self.typed.parse::<u16>() {
+            Ok(value) if value >= min && value <= max => {
+                let segment = self.segment;
+                self.set(segment, value);
+                self.typed.clear();
+                Ok(())
+            },
+            _ => Err(format!("{} must be between {} and {}", self.name(self.segment), min, max)),
+        }
+    }
+
+    /// Visible segments as displayed, and whether they are selected
+    pub fn segments(&self) -> Vec<(String, bool)> {
+        let mut segments = Vec::new();
+        for segment in 0..3 {
+            if ! self.visible[segment] {
+                continue;
+            }
+
+            let width = self.width(segment);
+            let string = if segment == self.segment && ! self.typed.is_empty() {
+                let mut string = self.typed.clone();
+                while string.len() < width {
+                    string.push('_');
+                }
+                string
+            } else {
+                format!("{:01$}", self.fields[segment], width)
+            };
+            segments.push((string, segment == self.segment));
+        }
+        segments
+    }
+
+    pub fn separator(&self) -> &'static str {
+        match self.kind {
+            DateTimeKind::Date => "/",
+            DateTimeKind::Time => ":",
+        }
+    }
+
+    pub fn string(&self) -> String {
+        let segments: Vec<String> = self.segments().into_iter().map(|(string, _)| string).collect();
+        segments.join(self.separator())
+    }
+
+    /// The fields to be submitted, or an error describing why they are invalid
+    pub fn value(&self) -> Result<[u16; 3], String> {
+        if ! self.typed.is_empty() {
+            let (min, max) = self.range(self.segment);
+            match self.typed.parse::<u16>() {
+                Ok(value) if value >= min && value <= max => {
+                    let mut fields = self.fields;
+                    fields[self.segment] = value;
+                    if self.kind == DateTimeKind::Date && fields[1] > days_in_month(fields[2], fields[0]) {
+                        return Err(format!("Day must be between 1 and {}", days_in_month(fields[2], fields[0])));
+                    }
+                    return Ok(fields);
+                },
+                _ => return Err(format!("{} must be between {} and {}", self.name(self.segment), min, max)),
+            }
+        }
+        Ok(self.fields)
+    }
+
+    /// Handle a key press, returning false if the key is not used by the editor
+    pub fn key(&mut self, key: &Key) -> bool {
+        let segment = self.segment;
+        match *key {
+            Key::Left => if self.commit().is_ok() {
+                self.move_segment(false);
+            },
+            Key::Right | Key::Tab => if self.commit().is_ok() {
+                self.move_segment(true);
+            },
+            Key::Up | Key::Character('+') => {
+                self.typed.clear();
+                let (min, max) = self.range(segment);
+                let value = self.fields[segment];
+                self.set(segment, if value >= max { min } else { value + 1 });
+            },
+            Key::Down | Key::Character('-') => {
+                self.typed.clear();
+                let (min, max) = self.range(segment);
+                let value = self.fields[segment];
+                self.set(segment, if value <= min { max } else { value - 1 });
+            },
+            Key::Backspace => {
+                self.typed.pop();
+            },
+            Key::Character(c) => if c.is_digit(10) {
+                if self.typed.len() >= self.width(segment) {
+                    self.typed.clear();
+                }
+                self.typed.push(c);
+                if self.typed.len() >= self.width(segment) && self.commit().is_ok() {
+                    self.move_segment(true);
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+}
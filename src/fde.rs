@@ -1,30 +1,50 @@
 use orbclient::{Color, Renderer};
 use orbfont::{Font, Text};
-use std::{char, cmp, ffi, mem, ptr, slice};
+use std::{char, cmp, ffi, iter, mem, ptr, slice};
+use std::collections::BTreeMap;
 use std::ops::Try;
 use std::proto::Protocol;
 use uefi::Event;
+use uefi::boot::{TimerDelay, Tpl};
+use uefi::graphics::GraphicsBltPixel;
 use uefi::guid::Guid;
 use uefi::hii::{AnimationId, ImageId, StringId};
 use uefi::hii::database::HiiHandle;
+use uefi::hii::package::{HiiPackageHeader, HiiPackageKind, HiiPackageListHeader};
 use uefi::hii::ifr::{
-    HiiValue,
-    IfrOpCode, IfrOpHeader, IfrStatementHeader, IfrTypeValueEnum,
+    HiiDate, HiiTime, HiiValue,
+    IfrOpCode, IfrOpHeader, IfrQuestionHeader, IfrStatementHeader, IfrTypeValueEnum,
     IfrAction, IfrCheckbox, IfrNumeric, IfrOneOf, IfrOneOfOption, IfrOrderedList, IfrRef, IfrSubtitle
 };
+use uefi::memory::MemoryType;
 use uefi::status::{Error, Result, Status};
 use uefi::text::TextInputKey;
 
-use crate::display::{Display, Output};
+use crate::accessibility::Accessibility;
+use crate::display::{scaled, Display, Output};
+use crate::hii;
+use crate::editor::{zero, zero_string, DateTimeEditor, DateTimeKind, LineEditor, Numeric, NumericDisplay, NumericEditor};
 use crate::image::{self, Image};
-use crate::key::{raw_key, Key};
+use crate::key::{raw_key_control, Key};
+use crate::pointer::{Pointer, PointerEvent};
+use crate::popup::Popup;
+use crate::resolution;
+use crate::theme::Theme;
 
 // TODO: Move to uefi library {
 pub const HII_STRING_PROTOCOL_GUID: Guid = Guid(0xfd96974, 0x23aa, 0x4cdc, [0xb9, 0xcb, 0x98, 0xd1, 0x77, 0x50, 0x32, 0x2a]);
 
 #[repr(C)]
 pub struct HiiStringProtocol {
-    pub NewString: extern "win64" fn(), //TODO
+    pub NewString: extern "win64" fn(
+        &HiiStringProtocol,
+        PackageList: HiiHandle,
+        StringId: &mut StringId,
+        Language: *const u8,
+        LanguageName: *const u16,
+        String: *const u16,
+        StringFontInfo: usize, // TODO
+    ) -> Status,
     pub GetString: extern "win64" fn(
         &HiiStringProtocol,
         Language: *const u8,
@@ -34,8 +54,20 @@ pub struct HiiStringProtocol {
         StringSize: &mut usize,
         StringFontInfo: usize, // TODO
     ) -> Status,
-    pub SetString: extern "win64" fn(), //TODO
-    pub GetLanguages: extern "win64" fn(), //TODO
+    pub SetString: extern "win64" fn(
+        &HiiStringProtocol,
+        PackageList: HiiHandle,
+        StringId: StringId,
+        Language: *const u8,
+        String: *const u16,
+        StringFontInfo: usize, // TODO
+    ) -> Status,
+    pub GetLanguages: extern "win64" fn(
+        &HiiStringProtocol,
+        PackageList: HiiHandle,
+        Languages: *mut u8,
+        LanguagesSize: &mut usize,
+    ) -> Status,
     pub GetSecondaryLanguages: extern "win64" fn(), //TODO
 }
 
@@ -64,6 +96,59 @@ impl HiiStringProtocol {
         }
         Ok(string)
     }
+
+    /// Languages of the strings in a package list, each null terminated
+    pub fn languages(&self, PackageList: HiiHandle) -> Result<Vec<Vec<u8>>> {
+        let mut data = vec![0u8; 1024];
+        let mut len = data.len();
+        (self.GetLanguages)(
+            self,
+            PackageList,
+            data.as_mut_ptr(),
+            &mut len
+        )?;
+        data.truncate(len);
+
+        // The languages are separated by semicolons
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Ok(data[..end].split(|&b| b == b';').filter(|language| ! language.is_empty()).map(|language| {
+            let mut language = language.to_vec();
+            language.push(0);
+            language
+        }).collect())
+    }
+
+    pub fn new_string(&self, PackageList: HiiHandle, language: &[u8], string: &str) -> Result<StringId> {
+        let mut data = wstr(string);
+        let mut id = 0;
+        let status = (self.NewString)(
+            self,
+            PackageList,
+            &mut id,
+            language.as_ptr(),
+            ptr::null(),
+            data.as_ptr(),
+            0
+        );
+        zero(&mut data);
+        status?;
+        Ok(id)
+    }
+
+    pub fn set_string(&self, PackageList: HiiHandle, StringId: StringId, language: &[u8], string: &str) -> Result<()> {
+        let mut data = wstr(string);
+        let status = (self.SetString)(
+            self,
+            PackageList,
+            StringId,
+            language.as_ptr(),
+            data.as_ptr(),
+            0
+        );
+        zero(&mut data);
+        status?;
+        Ok(())
+    }
 }
 
 impl Protocol<HiiStringProtocol> for &'static mut HiiStringProtocol {
@@ -76,6 +161,77 @@ impl Protocol<HiiStringProtocol> for &'static mut HiiStringProtocol {
     }
 }
 
+pub const HII_IMAGE_PROTOCOL_GUID: Guid = Guid(0x31a6406a, 0x6bdf, 0x4e46, [0xb2, 0xa2, 0xeb, 0xaa, 0x89, 0xc4, 0x09, 0x20]);
+
+pub const IMAGE_TRANSPARENT: u32 = 0x00000001;
+
+#[repr(C)]
+pub struct ImageInput {
+    pub Flags: u32,
+    pub Width: u16,
+    pub Height: u16,
+    pub Bitmap: *mut GraphicsBltPixel,
+}
+
+#[repr(C)]
+pub struct HiiImageProtocol {
+    pub NewImage: extern "win64" fn(), //TODO
+    pub GetImage: extern "win64" fn(
+        &HiiImageProtocol,
+        PackageList: HiiHandle,
+        ImageId: ImageId,
+        Image: &mut ImageInput,
+    ) -> Status,
+    pub SetImage: extern "win64" fn(), //TODO
+    pub DrawImage: extern "win64" fn(), //TODO
+    pub DrawImageId: extern "win64" fn(), //TODO
+}
+
+impl HiiImageProtocol {
+    pub fn image(&self, PackageList: HiiHandle, ImageId: ImageId) -> Result<Image> {
+        let mut input = ImageInput {
+            Flags: 0,
+            Width: 0,
+            Height: 0,
+            Bitmap: ptr::null_mut(),
+        };
+        (self.GetImage)(self, PackageList, ImageId, &mut input)?;
+
+        let transparent = input.Flags & IMAGE_TRANSPARENT != 0;
+        let len = input.Width as usize * input.Height as usize;
+        let mut data = Vec::with_capacity(len);
+        if ! input.Bitmap.is_null() {
+            let pixels = unsafe { slice::from_raw_parts(input.Bitmap, len) };
+            for pixel in pixels.iter() {
+                // Transparent images use black for the background
+                if transparent && pixel.Red == 0 && pixel.Green == 0 && pixel.Blue == 0 {
+                    data.push(Color::rgba(0, 0, 0, 0));
+                } else {
+                    data.push(Color::rgb(pixel.Red, pixel.Green, pixel.Blue));
+                }
+            }
+
+            let uefi = std::system_table();
+            let _ = (uefi.BootServices.FreePool)(input.Bitmap as usize);
+        }
+
+        Image::from_data(input.Width as u32, input.Height as u32, data.into_boxed_slice()).map_err(|err| {
+            debugln!("failed to create image {}: {}", ImageId, err);
+            Error::BadBufferSize
+        })
+    }
+}
+
+impl Protocol<HiiImageProtocol> for &'static mut HiiImageProtocol {
+    fn guid() -> Guid {
+        HII_IMAGE_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut HiiImageProtocol) -> Self {
+        inner
+    }
+}
+
 // } TODO: Move to uefi library
 
 // TODO: move to uefi library {
@@ -183,6 +339,75 @@ impl<T> ListHead<T> {
 }
 // } TODO: move to uefi library
 
+// TODO: move to uefi library {
+#[repr(C, packed)]
+pub struct IfrPassword {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub MinSize: u16,
+    pub MaxSize: u16,
+}
+
+#[repr(C, packed)]
+pub struct IfrString {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub MinSize: u8,
+    pub MaxSize: u8,
+    pub Flags: u8,
+}
+
+/// IfrNumeric with its minimum, maximum, and step, where T depends on the size flags
+#[repr(C, packed)]
+pub struct IfrNumericData<T> {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+    pub MinValue: T,
+    pub MaxValue: T,
+    pub Step: T,
+}
+
+pub const IFR_NUMERIC_SIZE: u8 = 0x03;
+pub const IFR_NUMERIC_SIZE_1: u8 = 0x00;
+pub const IFR_NUMERIC_SIZE_2: u8 = 0x01;
+pub const IFR_NUMERIC_SIZE_4: u8 = 0x02;
+
+pub const IFR_DISPLAY: u8 = 0x30;
+pub const IFR_DISPLAY_INT_DEC: u8 = 0x00;
+pub const IFR_DISPLAY_UINT_DEC: u8 = 0x10;
+pub const IFR_DISPLAY_UINT_HEX: u8 = 0x20;
+
+#[repr(C, packed)]
+pub struct IfrText {
+    pub Header: IfrOpHeader,
+    pub Statement: IfrStatementHeader,
+    pub TextTwo: StringId,
+}
+
+#[repr(C, packed)]
+pub struct IfrDate {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+}
+
+pub const QF_DATE_YEAR_SUPPRESS: u8 = 0x01;
+pub const QF_DATE_MONTH_SUPPRESS: u8 = 0x02;
+pub const QF_DATE_DAY_SUPPRESS: u8 = 0x04;
+
+#[repr(C, packed)]
+pub struct IfrTime {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+}
+
+pub const QF_TIME_HOUR_SUPPRESS: u8 = 0x01;
+pub const QF_TIME_MINUTE_SUPPRESS: u8 = 0x02;
+pub const QF_TIME_SECOND_SUPPRESS: u8 = 0x04;
+// } TODO: move to uefi library
+
 #[repr(C)]
 pub struct QuestionOption {
     pub Signature: usize,
@@ -296,6 +521,30 @@ const FRONT_PAGE_FORM_ID: u16 = 0x7600;
 const BROWSER_ACTION_NONE: u32 = 1 << 16;
 const BROWSER_ACTION_FORM_EXIT: u32 = 1 << 17;
 
+const HII_DISPLAY_GRAYOUT: u32 = 1 << 0;
+const HII_DISPLAY_LOCK: u32 = 1 << 1;
+const HII_DISPLAY_READONLY: u32 = 1 << 2;
+
+const BROWSER_ERROR: u32 = 1 << 31;
+const BROWSER_SUBMIT_FAIL: u32 = BROWSER_ERROR | 1;
+const BROWSER_NO_SUBMIT_IF: u32 = BROWSER_ERROR | 2;
+const BROWSER_FORM_NOT_FOUND: u32 = BROWSER_ERROR | 3;
+const BROWSER_FORM_SUPPRESS: u32 = BROWSER_ERROR | 4;
+const BROWSER_PROTOCOL_NOT_FOUND: u32 = BROWSER_ERROR | 5;
+const BROWSER_INCONSISTENT_IF: u32 = BROWSER_ERROR | 6;
+const BROWSER_WARNING_IF: u32 = BROWSER_ERROR | 7;
+const BROWSER_SUBMIT_FAIL_NO_SUBMIT_IF: u32 = BROWSER_ERROR | 8;
+const BROWSER_RECONNECT_REQUIRED: u32 = BROWSER_ERROR | 9;
+const BROWSER_RECONNECT_FAIL: u32 = BROWSER_ERROR | 10;
+const BROWSER_RECONNECT_SAVE_CHANGES: u32 = BROWSER_ERROR | 11;
+
+const BROWSER_SAVE_CHANGES: usize = 1;
+const BROWSER_DISCARD_CHANGES: usize = 2;
+const BROWSER_KEEP_CURRENT: usize = 3;
+
+// EFI_NOT_AVAILABLE_YET, returned by PasswordCheck when a password cannot be changed yet
+const STATUS_NOT_AVAILABLE_YET: usize = (1 << 63) | (1 << 61) | 2;
+
 #[repr(C)]
 pub struct UserInput {
     pub SelectedStatement: *const Statement,
@@ -317,88 +566,121 @@ static FONT_TTF: &'static [u8] = include_bytes!("../res/FiraSans-Regular.ttf");
 static CHECKBOX_CHECKED_BMP: &'static [u8] = include_bytes!("../res/checkbox_checked.bmp");
 static CHECKBOX_UNCHECKED_BMP: &'static [u8] = include_bytes!("../res/checkbox_unchecked.bmp");
 
-static mut DISPLAY: *mut Display = ptr::null_mut();
+static mut DISPLAYS: *mut Vec<Display> = ptr::null_mut();
 static mut FONT: *const Font = ptr::null_mut();
 static mut CHECKBOX_CHECKED: *const Image = ptr::null_mut();
 static mut CHECKBOX_UNCHECKED: *const Image = ptr::null_mut();
-
-struct ElementOption<'a> {
-    option_ptr: *const QuestionOption,
-    prompt: Text<'a>,
-    value: IfrTypeValueEnum,
-}
-
-struct Element<'a> {
-    statement_ptr: *const Statement,
-    prompt: String,
-    help: String,
-    value: IfrTypeValueEnum,
-    options: Vec<ElementOption<'a>>,
-    selectable: bool,
-    editable: bool,
-    list: bool,
-    list_i: usize,
-    buffer_opt: Option<&'static mut [u8]>,
-}
-
-#[derive(PartialEq)]
-enum EventType {
-    Driver,
-    Keyboard,
+static mut POINTER: *mut Pointer = ptr::null_mut();
+static mut TEXTS: *mut BTreeMap<u32, BTreeMap<String, Box<Text<'static>>>> = ptr::null_mut();
+static mut HII_IMAGES: *mut Vec<(HiiHandle, ImageId, Option<Box<Image>>)> = ptr::null_mut();
+static mut THEME: *const Theme = ptr::null();
+static mut ACCESSIBILITY: *mut Accessibility = ptr::null_mut();
+
+fn accessibility() -> &'static mut Accessibility {
+    unsafe {
+        if ACCESSIBILITY.is_null() {
+            ACCESSIBILITY = Box::into_raw(Box::new(Accessibility::load()));
+        }
+        &mut *ACCESSIBILITY
+    }
 }
 
-fn wait_for_events(form: &Form) -> Result<EventType>  {
-    let uefi = std::system_table();
-    let mut index = 0;
-    let mut events = Vec::new();
-
-    events.push(uefi.ConsoleIn.WaitForKey);
+/// Handle the resolution hotkey, Ctrl+R, which switches to the next resolution and saves it
+fn resolution_key(raw_key: &TextInputKey, control: bool, display: &mut Display) -> bool {
+    if raw_key.ScanCode != 0 {
+        return false;
+    }
 
-    if form.FormRefreshEvent != Event(0) {
-        events.push(form.FormRefreshEvent);
+    match (char::from_u32(raw_key.UnicodeChar as u32).unwrap_or('\0'), control) {
+        // Consoles turn Ctrl+R into a control character, even without reporting control
+        ('\u{12}', _) | ('r', true) | ('R', true) => {
+            resolution::cycle(display);
+            true
+        },
+        _ => false,
     }
+}
 
-    (uefi.BootServices.WaitForEvent)(events.len(), events.as_mut_ptr(), &mut index)?;
+/// The loaded theme, with the high contrast palette if it is enabled
+fn theme() -> Theme {
+    let theme = unsafe {
+        // Loaded on first use, once the file systems have been connected
+        if THEME.is_null() {
+            THEME = Box::into_raw(Box::new(Theme::load()));
+        }
+        &*THEME
+    };
 
-    if index == 0 {
-        Ok(EventType::Keyboard)
+    if accessibility().high_contrast {
+        theme.high_contrast()
     } else {
-        Ok(EventType::Driver)
+        theme.clone()
     }
 }
 
-#[allow(unused_assignments)]
-fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
-    debugln!();
-    debugln!("form_display");
-    debugln!("FORM_DISPLAY_ENGINE_FORM {}, {:?}", mem::size_of::<Form>(), span_of!(Form, HotKeyListHead));
-    debugln!("BROWSER_HOT_KEY {}, {:?}", mem::size_of::<HotKey>(), span_of!(HotKey, Link));
-
-    let hii_string = <&'static mut HiiStringProtocol>::one()?;
+/// Handle the accessibility hotkeys, returning true if the key was one of them.
+/// Ctrl+A toggles high contrast, Ctrl+Plus and Ctrl+Minus zoom, and Ctrl+0 resets the zoom.
+/// `zoom_limits` has the unzoomed scale of each display and the largest scale that still leaves
+/// room for a row of the form body, zooming in past it is refused
+fn accessibility_key(raw_key: &TextInputKey, control: bool, zoom_limits: &[(f32, f32)]) -> bool {
+    if raw_key.ScanCode != 0 {
+        return false;
+    }
 
-    let string = |string_id: StringId| -> Result<String> {
-         hii_string.string(form.HiiHandle, string_id)
+    let fits = |accessibility: &Accessibility| {
+        zoom_limits.iter().all(|&(scale, max_scale)| accessibility.scale(scale) <= max_scale)
     };
 
-    let mut display = unsafe {
-        if DISPLAY.is_null() {
-            let display = Display::new(Output::one()?);
-            DISPLAY = Box::into_raw(Box::new(display));
-        }
-        &mut *DISPLAY
+    let accessibility = accessibility();
+    let changed = match (char::from_u32(raw_key.UnicodeChar as u32).unwrap_or('\0'), control) {
+        // Consoles turn Ctrl+A into a control character, even without reporting control
+        ('\u{1}', _) | ('a', true) | ('A', true) => {
+            accessibility.high_contrast = ! accessibility.high_contrast;
+            true
+        },
+        ('+', true) | ('=', true) => accessibility.zoom_by(1, fits),
+        ('-', true) | ('_', true) => accessibility.zoom_by(-1, fits),
+        ('0', true) => accessibility.zoom_by(-accessibility.zoom, fits),
+        _ => return false,
     };
 
-    let (display_w, display_h) = (display.width(), display.height());
+    if changed {
+        accessibility.save();
+    }
+    true
+}
 
-    let scale = if display_h > 1440 {
-        4
-    } else if display_h > 720 {
-        2
-    } else {
-        1
-    };
+fn pointer() -> &'static mut Pointer {
+    unsafe {
+        if POINTER.is_null() {
+            POINTER = Box::into_raw(Box::new(Pointer::new()));
+        }
+        &mut *POINTER
+    }
+}
+
+/// A display for every graphics output, the UI is mirrored to all of them
+fn displays() -> Result<&'static mut Vec<Display>> {
+    unsafe {
+        if DISPLAYS.is_null() {
+            let mut displays = Vec::new();
+            for output in Output::physical() {
+                let mut display = Display::new(output);
+                // The first output is the primary one
+                resolution::select(&mut display, displays.is_empty());
+                displays.push(display);
+            }
+            if displays.is_empty() {
+                return Err(Error::NotFound);
+            }
+            DISPLAYS = Box::into_raw(Box::new(displays));
+        }
+        Ok(&mut *DISPLAYS)
+    }
+}
 
-    let font = unsafe {
+fn font() -> Result<&'static Font> {
+    unsafe {
         if FONT.is_null() {
             let font = match Font::from_data(FONT_TTF) {
                 Ok(ok) => ok,
@@ -409,101 +691,783 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
             };
             FONT = Box::into_raw(Box::new(font));
         }
-        &*FONT
-    };
+        Ok(&*FONT)
+    }
+}
 
-    let checkbox_checked = unsafe {
-        if CHECKBOX_CHECKED.is_null() {
-            let image = match image::bmp::parse(CHECKBOX_CHECKED_BMP) {
-                Ok(ok) => ok,
-                Err(err) => {
-                    println!("failed to parse checkbox checked: {}", err);
-                    return Err(Error::NotFound);
-                }
+unsafe fn cached_image(cache: &mut *const Image, data: &[u8], name: &str) -> Result<&'static Image> {
+    if cache.is_null() {
+        let image = match image::bmp::parse(data) {
+            Ok(ok) => ok,
+            Err(err) => {
+                println!("failed to parse {}: {}", name, err);
+                return Err(Error::NotFound);
+            }
+        };
+        *cache = Box::into_raw(Box::new(image));
+    }
+    Ok(&**cache)
+}
+
+/// Decode an image from the image package of an HII package list, for when the image protocol is missing
+fn decode_hii_image(handle: HiiHandle, image_id: ImageId) -> Result<Image> {
+    for db in hii::Database::all() {
+        let mut size = 0;
+
+        match (db.0.ExportPackageLists)(
+            db.0,
+            handle,
+            &mut size,
+            unsafe { &mut *ptr::null_mut() }
+        ).into_result() {
+            Ok(_) => (),
+            Err(err) if err == Error::BufferTooSmall => (),
+            Err(err) => return Err(err),
+        }
+
+        let mut data: Box<[u8]> = vec![0; size].into_boxed_slice();
+        (db.0.ExportPackageLists)(
+            db.0,
+            handle,
+            &mut size,
+            unsafe { &mut *(data.as_mut_ptr() as *mut HiiPackageListHeader) }
+        )?;
+
+        let mut i = mem::size_of::<HiiPackageListHeader>();
+        while i + mem::size_of::<HiiPackageHeader>() < data.len() {
+            let package = unsafe {
+                & *(data.as_ptr().add(i) as *const HiiPackageHeader)
             };
-            CHECKBOX_CHECKED = Box::into_raw(Box::new(image));
+            let length = package.Length() as usize;
+            if length == 0 || i + length > data.len() {
+                break;
+            }
+
+            if package.Kind() == HiiPackageKind::Images {
+                return image::hii::parse(&data[i..i + length], image_id).map_err(|err| {
+                    debugln!("failed to parse image {}: {}", image_id, err);
+                    Error::NotFound
+                });
+            }
+
+            i += length;
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Get an image from the HII database, images are cached until the display engine exits
+fn hii_image(handle: HiiHandle, image_id: ImageId) -> Option<&'static Image> {
+    if image_id == 0 {
+        return None;
+    }
+
+    let images = unsafe {
+        if HII_IMAGES.is_null() {
+            HII_IMAGES = Box::into_raw(Box::new(Vec::new()));
         }
-        &*CHECKBOX_CHECKED
+        &mut *HII_IMAGES
     };
 
-    let checkbox_unchecked = unsafe {
-        if CHECKBOX_UNCHECKED.is_null() {
-            let image = match image::bmp::parse(CHECKBOX_UNCHECKED_BMP) {
-                Ok(ok) => ok,
-                Err(err) => {
-                    println!("failed to parse checkbox unchecked: {}", err);
-                    return Err(Error::NotFound);
-                }
-            };
-            CHECKBOX_UNCHECKED = Box::into_raw(Box::new(image));
+    if let Some(cached) = images.iter().find(|cached| cached.0 == handle && cached.1 == image_id) {
+        return cached.2.as_ref().map(|image| unsafe { &*(image.as_ref() as *const Image) });
+    }
+
+    let image_result = match <&'static mut HiiImageProtocol>::one() {
+        Ok(hii_image) => hii_image.image(handle, image_id),
+        Err(_) => decode_hii_image(handle, image_id),
+    };
+
+    let image_opt = match image_result {
+        Ok(image) => Some(Box::new(image)),
+        Err(err) => {
+            debugln!("failed to get image {}: {:?}", image_id, err);
+            None
+        }
+    };
+    let image_ptr = image_opt.as_ref().map(|image| image.as_ref() as *const Image);
+    images.push((handle, image_id, image_opt));
+    image_ptr.map(|image| unsafe { &*image })
+}
+
+/// Shrink an image to fit within a height, keeping its aspect ratio
+fn fit_image(image: &Image, h: u32) -> Image {
+    if image.height() > h && image.height() > 0 {
+        let w = cmp::max(image.width() * h / image.height(), 1);
+        image.resize(w, h)
+    } else {
+        image.clone()
+    }
+}
+
+// Number of cached texts above which the cache is emptied when a form is displayed
+const TEXTS_MAX: usize = 4096;
+
+/// Render text with the font, caching it by string and size.
+/// Texts stay valid until trim_texts, which is only called before a form is displayed, so this
+/// is only for text that does not change while a form is shown. Values and the text being
+/// edited are rendered directly, or the cache would grow with every key press
+fn text(font: &'static Font, string: &str, font_size: f32) -> &'static Text<'static> {
+    let texts = unsafe {
+        if TEXTS.is_null() {
+            TEXTS = Box::into_raw(Box::new(BTreeMap::new()));
         }
-        &*CHECKBOX_UNCHECKED
+        &mut *TEXTS
     };
 
-    // Style {
-    let background_color = Color::rgb(0x36, 0x32, 0x2F);
-    let highlight_color = Color::rgb(0xFB, 0xB8, 0x6C);
-    let outline_color = Color::rgba(0xfe, 0xff, 0xff, 0xc4);
-    let text_color = Color::rgb(0xCC, 0xCC, 0xCC);
-    let highlight_text_color = Color::rgb(0x27, 0x27, 0x27);
+    let sized = texts.entry(font_size.to_bits()).or_insert_with(BTreeMap::new);
+    if let Some(rendered) = sized.get(string) {
+        return unsafe { &*(rendered.as_ref() as *const Text) };
+    }
 
-    let padding_lr = 4 * scale;
-    let padding_tb = 2 * scale;
+    let rendered = Box::new(font.render(string, font_size));
+    let rendered_ptr = rendered.as_ref() as *const Text;
+    sized.insert(string.to_string(), rendered);
+    unsafe { &*rendered_ptr }
+}
 
-    let margin_lr = 8 * scale;
-    let margin_tb = 4 * scale;
+/// Empty the text cache if it has grown too large, this invalidates all cached texts
+fn trim_texts() {
+    unsafe {
+        if ! TEXTS.is_null() {
+            let count: usize = (*TEXTS).values().map(|sized| sized.len()).sum();
+            if count > TEXTS_MAX {
+                debugln!("trimming {} cached texts", count);
+                (*TEXTS).clear();
+            }
+        }
+    }
+}
 
-    let rect_radius = 4; //TODO: does not scale due to hardcoded checkbox image!
+fn display_scale(display: &Display) -> f32 {
+    accessibility().scale(display.scale())
+}
 
-    let title_font_size = (20  * scale) as f32;
-    let font_size = (16 * scale) as f32; // (display_h as f32) / 26.0
-    let help_font_size = (12 * scale) as f32;
-    // } Style
+struct ElementOption<'a> {
+    option_ptr: *const QuestionOption,
+    prompt: String,
+    image_id: ImageId,
+    rendered: Option<&'a Text<'a>>,
+    image: Option<Image>,
+    value: IfrTypeValueEnum,
+}
 
-    'render: loop {
-        let mut hotkey_helps = Vec::new();
-        for hotkey in form.HotKeyListHead.iter() {
-            let hotkey_ptr = hotkey as *const _;
-            debugln!("  hotkey: {:p}, {:x?}", hotkey_ptr, unsafe {
-                slice::from_raw_parts(
-                    hotkey_ptr as *const u8,
-                    mem::size_of_val(hotkey)
-                )
+struct Element<'a> {
+    statement_ptr: *const Statement,
+    prompt: String,
+    help: String,
+    image_id: ImageId,
+    rendered: Option<&'a Text<'a>>,
+    // Font size the element was laid out at, outputs may use different sizes
+    rendered_size: f32,
+    image: Option<Image>,
+    value: IfrTypeValueEnum,
+    options: Vec<ElementOption<'a>>,
+    selectable: bool,
+    editable: bool,
+    grayout: bool,
+    read_only: bool,
+    list: bool,
+    list_i: usize,
+    password: bool,
+    string: bool,
+    numeric: Option<Numeric>,
+    date_time: Option<[bool; 3]>,
+    text_two: Option<String>,
+    depth: usize,
+    buffer_opt: Option<&'static mut [u8]>,
+}
+
+impl<'a> Element<'a> {
+    /// Render the prompts and load the images of an element, only done once it is shown
+    fn layout(&mut self, font: &'static Font, font_size: f32, handle: HiiHandle) {
+        if self.rendered.is_some() && self.rendered_size == font_size {
+            return;
+        }
+
+        self.rendered = Some(text(font, &self.prompt, font_size));
+        self.rendered_size = font_size;
+        self.image = hii_image(handle, self.image_id).map(|image| {
+            fit_image(image, font_size as u32)
+        });
+        for option in self.options.iter_mut() {
+            option.rendered = Some(text(font, &option.prompt, font_size));
+            option.image = hii_image(handle, option.image_id).map(|image| {
+                fit_image(image, font_size as u32)
             });
-            debugln!("    key: {:p}, {:?}", hotkey.KeyData, unsafe { *hotkey.KeyData });
-            debugln!("    action: {:#x}", hotkey.Action);
-            debugln!("    defaultid: {:#x}", hotkey.DefaultId);
-            let hotkey_help = ffi::nstr(hotkey.HelpString);
-            debugln!("    help: {:p}, {}", hotkey.HelpString, hotkey_help);
-            hotkey_helps.push(hotkey_help);
         }
+    }
+}
 
-        let mut selected = !0;
-        let mut editing = false;
-        let mut elements = Vec::new();
-        for statement in form.StatementListHead.iter() {
-            let statement_ptr = statement as *const _;
-            debugln!("statement: {:p}", statement_ptr);
+#[derive(Clone, Copy, PartialEq)]
+enum PasswordStage {
+    Old,
+    New,
+    Confirm,
+}
 
-            let mut options = Vec::new();
-            for option in statement.OptionListHead.iter() {
-                let option_ptr = option as *const _;
-                debugln!("  option: {:p}", option_ptr);
-                if let Some(op) = option.OptionOpCode() {
-                    let value = unsafe {
-                        op.Value.to_enum(op.Kind)
-                    };
-                    debugln!("    {:?}: {:?}", op.Option, value);
-                    let prompt = font.render(&string(op.Option).unwrap_or(String::new()), font_size);
-                    options.push(ElementOption {
-                        option_ptr,
-                        prompt,
-                        value,
-                    });
+struct PasswordInput {
+    stage: PasswordStage,
+    editor: LineEditor,
+    new: String,
+}
+
+impl PasswordInput {
+    fn new(stage: PasswordStage, min: usize, max: usize) -> Self {
+        Self {
+            stage,
+            editor: LineEditor::new("", min, max),
+            new: String::new(),
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self.stage {
+            PasswordStage::Old => "Enter Current Password",
+            PasswordStage::New => "Enter New Password",
+            PasswordStage::Confirm => "Confirm New Password",
+        }
+    }
+
+    /// Check the typed password with the driver, without leaving a copy of it in memory
+    fn check(&self, form: &Form, statement: &Statement) -> Status {
+        let mut entered = self.editor.string();
+        let status = password_check(form, statement, Some(&entered));
+        zero_string(&mut entered);
+        status
+    }
+
+    /// Returns true if the typed password matches the new password
+    fn confirmed(&self) -> bool {
+        self.editor.chars().iter().cloned().eq(self.new.chars())
+    }
+}
+
+impl Drop for PasswordInput {
+    fn drop(&mut self) {
+        zero_string(&mut self.new);
+    }
+}
+
+enum Editor {
+    Password(PasswordInput),
+    String(LineEditor),
+    Numeric(NumericEditor),
+    DateTime(DateTimeEditor),
+}
+
+impl Editor {
+    /// Handle a key press, returning false if the key is not used by the editor
+    fn key(&mut self, key: &Key) -> bool {
+        match self {
+            Editor::Password(password) => password.editor.key(key),
+            Editor::String(editor) => editor.key(key),
+            Editor::Numeric(editor) => editor.key(key),
+            Editor::DateTime(editor) => editor.key(key),
+        }
+    }
+
+    /// Error to display for the value being edited
+    fn error(&self) -> Option<String> {
+        match self {
+            Editor::Numeric(editor) => editor.value().err(),
+            Editor::DateTime(editor) => editor.value().err(),
+            _ => None,
+        }
+    }
+}
+
+fn numeric_info(op: &IfrOpHeader) -> Option<Numeric> {
+    match op.OpCode {
+        IfrOpCode::Numeric => (),
+        _ => return None,
+    }
+
+    let flags = unsafe { op.cast::<IfrNumericData<u8>>() }?.Flags;
+
+    macro_rules! limits {
+        ($type:ty) => ({
+            let data = unsafe { op.cast::<IfrNumericData<$type>>() }?;
+            (
+                mem::size_of::<$type>() as u32,
+                data.MinValue as u64,
+                data.MaxValue as u64,
+                data.Step as u64
+            )
+        });
+    }
+    let (size, min, max, step) = match flags & IFR_NUMERIC_SIZE {
+        IFR_NUMERIC_SIZE_1 => limits!(u8),
+        IFR_NUMERIC_SIZE_2 => limits!(u16),
+        IFR_NUMERIC_SIZE_4 => limits!(u32),
+        _ => limits!(u64),
+    };
+
+    let display = match flags & IFR_DISPLAY {
+        IFR_DISPLAY_INT_DEC => NumericDisplay::Signed,
+        IFR_DISPLAY_UINT_DEC => NumericDisplay::Unsigned,
+        IFR_DISPLAY_UINT_HEX => NumericDisplay::Hex,
+        _ => NumericDisplay::Unsigned,
+    };
+
+    Some(Numeric {
+        size,
+        display,
+        min,
+        max,
+        step,
+    })
+}
+
+/// Visible date or time fields, in display order
+fn date_time_visible(op: &IfrOpHeader) -> Option<[bool; 3]> {
+    match op.OpCode {
+        IfrOpCode::Date => {
+            let flags = unsafe { op.cast::<IfrDate>() }?.Flags;
+            Some([
+                flags & QF_DATE_MONTH_SUPPRESS == 0,
+                flags & QF_DATE_DAY_SUPPRESS == 0,
+                flags & QF_DATE_YEAR_SUPPRESS == 0,
+            ])
+        },
+        IfrOpCode::Time => {
+            let flags = unsafe { op.cast::<IfrTime>() }?.Flags;
+            Some([
+                flags & QF_TIME_HOUR_SUPPRESS == 0,
+                flags & QF_TIME_MINUTE_SUPPRESS == 0,
+                flags & QF_TIME_SECOND_SUPPRESS == 0,
+            ])
+        },
+        _ => None,
+    }
+}
+
+fn date_time_editor(value: &IfrTypeValueEnum, visible: [bool; 3]) -> Option<DateTimeEditor> {
+    match *value {
+        IfrTypeValueEnum::Date(date) => Some(DateTimeEditor::new(
+            DateTimeKind::Date,
+            [date.Month as u16, date.Day as u16, date.Year],
+            visible
+        )),
+        IfrTypeValueEnum::Time(time) => Some(DateTimeEditor::new(
+            DateTimeKind::Time,
+            [time.Hour as u16, time.Minute as u16, time.Second as u16],
+            visible
+        )),
+        _ => None,
+    }
+}
+
+fn date_time_value_enum(kind: DateTimeKind, fields: [u16; 3]) -> IfrTypeValueEnum {
+    match kind {
+        DateTimeKind::Date => IfrTypeValueEnum::Date(HiiDate {
+            Year: fields[2],
+            Month: fields[0] as u8,
+            Day: fields[1] as u8,
+        }),
+        DateTimeKind::Time => IfrTypeValueEnum::Time(HiiTime {
+            Hour: fields[0] as u8,
+            Minute: fields[1] as u8,
+            Second: fields[2] as u8,
+        }),
+    }
+}
+
+fn numeric_value(value: &IfrTypeValueEnum) -> Option<u64> {
+    match *value {
+        IfrTypeValueEnum::U8(value) => Some(value as u64),
+        IfrTypeValueEnum::U16(value) => Some(value as u64),
+        IfrTypeValueEnum::U32(value) => Some(value as u64),
+        IfrTypeValueEnum::U64(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn numeric_value_enum(numeric: &Numeric, value: u64) -> IfrTypeValueEnum {
+    match numeric.size {
+        1 => IfrTypeValueEnum::U8(value as u8),
+        2 => IfrTypeValueEnum::U16(value as u16),
+        4 => IfrTypeValueEnum::U32(value as u32),
+        _ => IfrTypeValueEnum::U64(value),
+    }
+}
+
+/// Convert a string to a null terminated UCS-2 string
+fn wstr(string: &str) -> Vec<u16> {
+    string.encode_utf16().chain(iter::once(0)).collect()
+}
+
+/// Copy data into pool memory, so that the browser can free it
+fn allocate_pool_copy(data: &[u8]) -> Result<*mut u8> {
+    let uefi = std::system_table();
+
+    let mut address = 0;
+    (uefi.BootServices.AllocatePool)(MemoryType::EfiBootServicesData, data.len(), &mut address)?;
+
+    let buffer = address as *mut u8;
+    unsafe {
+        ptr::copy(data.as_ptr(), buffer, data.len());
+    }
+    Ok(buffer)
+}
+
+fn form_error(form: &Form) -> Option<String> {
+    if form.ErrorString.is_null() {
+        return None;
+    }
+
+    let error = ffi::nstr(form.ErrorString);
+    if error.trim().is_empty() {
+        None
+    } else {
+        Some(error)
+    }
+}
+
+/// Message for a browser status, used when the browser does not provide an error string
+fn browser_status_message(status: u32) -> String {
+    match status {
+        BROWSER_SUBMIT_FAIL => "Failed to submit changes".to_string(),
+        BROWSER_NO_SUBMIT_IF => "Changes cannot be submitted".to_string(),
+        BROWSER_FORM_NOT_FOUND => "Form not found".to_string(),
+        BROWSER_FORM_SUPPRESS => "Form is suppressed".to_string(),
+        BROWSER_PROTOCOL_NOT_FOUND => "Protocol not found".to_string(),
+        BROWSER_INCONSISTENT_IF => "Value is inconsistent".to_string(),
+        BROWSER_WARNING_IF => "Value is not recommended".to_string(),
+        BROWSER_SUBMIT_FAIL_NO_SUBMIT_IF => "Failed to submit changes, some values are not valid".to_string(),
+        BROWSER_RECONNECT_REQUIRED => "Changes require a reconnect to take effect".to_string(),
+        BROWSER_RECONNECT_FAIL => "Failed to reconnect".to_string(),
+        BROWSER_RECONNECT_SAVE_CHANGES => "Changes must be saved before reconnecting".to_string(),
+        other => format!("Browser error {:#x}", other),
+    }
+}
+
+/// Fill in a string or password input value, using a UCS-2 pool buffer the size of the current value
+fn string_input_value(hii_string: &HiiStringProtocol, form: &Form, statement: &Statement, string: &str, input_value: &mut HiiValue) -> Result<()> {
+    unsafe {
+        ptr::copy(
+            &statement.CurrentValue,
+            input_value,
+            1
+        );
+    }
+
+    // The string may be a password, so the copies made here are zeroed
+    let mut data = wstr(string);
+    let mut buffer = vec![0; statement.CurrentValue.BufferLen as usize];
+    for (i, w) in data.iter().enumerate() {
+        if i * 2 + 2 <= buffer.len() {
+            buffer[i * 2] = *w as u8;
+            buffer[i * 2 + 1] = (*w >> 8) as u8;
+        }
+    }
+    let buffer_result = allocate_pool_copy(&buffer);
+    zero(&mut data);
+    zero(&mut buffer);
+    input_value.Buffer = buffer_result?;
+
+    // Update the string of the question in place in every language, like HiiSetString, so that
+    // every edit does not add another string to the package list
+    let languages = hii_string.languages(form.HiiHandle)?;
+    if languages.is_empty() {
+        return Err(Error::NotFound);
+    }
+    let mut string_id = unsafe { statement.CurrentValue.Value.string };
+    for language in languages.iter() {
+        if string_id == 0 {
+            string_id = hii_string.new_string(form.HiiHandle, language, string)?;
+        } else {
+            hii_string.set_string(form.HiiHandle, string_id, language, string)?;
+        }
+    }
+    let (kind, value) = unsafe {
+        IfrTypeValueEnum::String(string_id).to_union()
+    };
+    input_value.Kind = kind;
+    input_value.Value = value;
+
+    Ok(())
+}
+
+fn password_check(form: &Form, statement: &Statement, password_opt: Option<&str>) -> Status {
+    match statement.PasswordCheck {
+        Some(check) => {
+            let mut data_opt = password_opt.map(wstr);
+            let data_ptr = match data_opt {
+                Some(ref data) => data.as_ptr(),
+                None => ptr::null(),
+            };
+            let status = (check)(form, statement, data_ptr);
+            if let Some(ref mut data) = data_opt {
+                zero(data);
+            }
+            status
+        },
+        None => Status::from_error(Error::Unsupported),
+    }
+}
+
+/// Walk statements and their nested statements in display order, with the nesting depth of each
+fn flatten_statements<'a>(list: &'a ListHead<Statement>, depth: usize, statements: &mut Vec<(&'a Statement, usize)>) {
+    for statement in list.iter() {
+        statements.push((statement, depth));
+        flatten_statements(&statement.NestStatementList, depth + 1, statements);
+    }
+}
+
+/// Find the closest selectable element to index i, searching forward or backward from it
+fn selectable_from(elements: &[Element], i: usize, forward: bool) -> Option<usize> {
+    if forward {
+        (i..elements.len()).find(|&j| elements[j].selectable)
+    } else {
+        (0..cmp::min(i + 1, elements.len())).rev().find(|&j| elements[j].selectable)
+    }
+}
+
+/// Move the selection to the next or previous selectable element, wrapping around at either end.
+/// The selection is kept if no other element is selectable
+fn step_selectable<F: Fn(usize) -> bool>(len: usize, selected: usize, forward: bool, selectable: F) -> usize {
+    if len == 0 {
+        return selected;
+    }
+
+    let mut i = selected;
+    loop {
+        i = match forward {
+            true if i + 1 < len => i + 1,
+            true => 0,
+            false if i > 0 => i - 1,
+            false => len - 1,
+        };
+        if i == selected || selectable(i) {
+            return i;
+        }
+    }
+}
+
+/// Adjust the first displayed element so that the selected element is visible
+fn visible_start(selected: usize, element_start: usize, max_form_elements: usize) -> usize {
+    if selected < element_start {
+        selected
+    } else if selected >= element_start + max_form_elements {
+        selected + 1 - cmp::max(max_form_elements, 1)
+    } else {
+        element_start
+    }
+}
+
+// Seconds without typing before a search starts over
+const SEARCH_TIMEOUT: u64 = 2;
+
+// TODO: move to uefi library {
+pub const EVT_TIMER: u32 = 0x80000000;
+pub const TPL_APPLICATION: Tpl = Tpl(4);
+// } TODO: move to uefi library
+
+/// Timer that ends a search after a pause in typing
+struct SearchTimer(Event);
+
+impl SearchTimer {
+    fn new() -> Result<Self> {
+        let uefi = std::system_table();
+        let mut event = Event(0);
+        (uefi.BootServices.CreateEvent)(EVT_TIMER, TPL_APPLICATION, None, 0, &mut event)?;
+        Ok(SearchTimer(event))
+    }
+
+    /// Start the timeout over, when a character of the search is typed
+    fn restart(&self) {
+        let uefi = std::system_table();
+        // The trigger time is in units of 100 nanoseconds
+        let status = (uefi.BootServices.SetTimer)(self.0, TimerDelay::Relative, SEARCH_TIMEOUT * 10_000_000);
+        if let Err(err) = status.into_result() {
+            debugln!("failed to set search timer: {:?}", err);
+        }
+    }
+
+    /// True if the timeout passed since the last restart
+    fn expired(&self) -> bool {
+        let uefi = std::system_table();
+        (uefi.BootServices.CheckEvent)(self.0).into_result().is_ok()
+    }
+}
+
+impl Drop for SearchTimer {
+    fn drop(&mut self) {
+        let uefi = std::system_table();
+        let _ = (uefi.BootServices.CloseEvent)(self.0);
+    }
+}
+
+/// Find search text in a prompt ignoring case, returning the byte range of the match
+fn search_match(prompt: &str, search: &str) -> Option<(usize, usize)> {
+    if search.is_empty() {
+        return None;
+    }
+
+    for (start, _) in prompt.char_indices() {
+        let mut end = start;
+        let mut chars = prompt[start..].chars();
+        let matches = search.chars().all(|search_c| match chars.next() {
+            Some(c) => {
+                end += c.len_utf8();
+                c.to_lowercase().eq(search_c.to_lowercase())
+            },
+            None => false,
+        });
+        if matches {
+            return Some((start, end));
+        }
+    }
+
+    None
+}
+
+#[derive(PartialEq)]
+enum EventType {
+    Driver,
+    Keyboard,
+    Pointer,
+}
+
+fn wait_for_events(form: &Form, pointer: &Pointer) -> Result<EventType>  {
+    let uefi = std::system_table();
+    let mut index = 0;
+    let mut events = Vec::new();
+
+    events.push(uefi.ConsoleIn.WaitForKey);
+
+    let pointer_events = pointer.events();
+    events.extend_from_slice(&pointer_events);
+
+    if form.FormRefreshEvent != Event(0) {
+        events.push(form.FormRefreshEvent);
+    }
+
+    (uefi.BootServices.WaitForEvent)(events.len(), events.as_mut_ptr(), &mut index)?;
+
+    if index == 0 {
+        Ok(EventType::Keyboard)
+    } else if index <= pointer_events.len() {
+        Ok(EventType::Pointer)
+    } else {
+        Ok(EventType::Driver)
+    }
+}
+
+#[allow(unused_assignments)]
+fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
+    debugln!();
+    debugln!("form_display");
+    debugln!("FORM_DISPLAY_ENGINE_FORM {}, {:?}", mem::size_of::<Form>(), span_of!(Form, HotKeyListHead));
+    debugln!("BROWSER_HOT_KEY {}, {:?}", mem::size_of::<HotKey>(), span_of!(HotKey, Link));
+
+    let hii_string = <&'static mut HiiStringProtocol>::one()?;
+
+    let string = |string_id: StringId| -> Result<String> {
+         hii_string.string(form.HiiHandle, string_id)
+    };
+
+    let displays = displays()?;
+
+    // Something else may have drawn to the screen since the last form was shown
+    for display in displays.iter_mut() {
+        display.invalidate();
+    }
+
+    let font = font()?;
+
+    // No cached texts are in use until the form is laid out
+    trim_texts();
+
+    let pointer = pointer();
+
+    let checkbox_checked = unsafe {
+        cached_image(&mut CHECKBOX_CHECKED, CHECKBOX_CHECKED_BMP, "checkbox checked")?
+    };
+
+    let checkbox_unchecked = unsafe {
+        cached_image(&mut CHECKBOX_UNCHECKED, CHECKBOX_UNCHECKED_BMP, "checkbox unchecked")?
+    };
+
+    // Report the result of the last action before the form is redrawn
+    if form.BrowserStatus != 0 {
+        debugln!("browser status: {:#x}", form.BrowserStatus);
+        let title = if form.BrowserStatus == BROWSER_WARNING_IF {
+            "Warning"
+        } else {
+            "Error"
+        };
+        let message = form_error(form).unwrap_or_else(|| browser_status_message(form.BrowserStatus));
+        alert(displays, font, title, &message)?;
+    } else if let Some(error) = form_error(form) {
+        alert(displays, font, "Error", &error)?;
+    }
+
+    'render: loop {
+        let mut hotkey_helps = Vec::new();
+        for hotkey in form.HotKeyListHead.iter() {
+            let hotkey_ptr = hotkey as *const _;
+            debugln!("  hotkey: {:p}, {:x?}", hotkey_ptr, unsafe {
+                slice::from_raw_parts(
+                    hotkey_ptr as *const u8,
+                    mem::size_of_val(hotkey)
+                )
+            });
+            debugln!("    key: {:p}, {:?}", hotkey.KeyData, unsafe { *hotkey.KeyData });
+            debugln!("    action: {:#x}", hotkey.Action);
+            debugln!("    defaultid: {:#x}", hotkey.DefaultId);
+            let hotkey_help = ffi::nstr(hotkey.HelpString);
+            debugln!("    help: {:p}, {}", hotkey.HelpString, hotkey_help);
+            hotkey_helps.push(hotkey_help);
+        }
+
+        let mut selected = !0;
+        let mut editing = false;
+        let mut editor_opt: Option<Editor> = None;
+        let mut error_opt: Option<String> = None;
+        let mut search = String::new();
+        let search_timer_opt = match SearchTimer::new() {
+            Ok(ok) => Some(ok),
+            Err(err) => {
+                debugln!("failed to create search timer: {:?}", err);
+                None
+            }
+        };
+        let mut elements = Vec::new();
+        let mut statements = Vec::new();
+        flatten_statements(&form.StatementListHead, 0, &mut statements);
+        for (statement, depth) in statements {
+            let statement_ptr = statement as *const _;
+            debugln!("statement: {:p}", statement_ptr);
+
+            let mut options = Vec::new();
+            for option in statement.OptionListHead.iter() {
+                let option_ptr = option as *const _;
+                debugln!("  option: {:p}", option_ptr);
+                if let Some(op) = option.OptionOpCode() {
+                    let value = unsafe {
+                        op.Value.to_enum(op.Kind)
+                    };
+                    debugln!("    {:?}: {:?}", op.Option, value);
+                    options.push(ElementOption {
+                        option_ptr,
+                        prompt: string(op.Option).unwrap_or(String::new()),
+                        image_id: option.ImageId,
+                        rendered: None,
+                        image: None,
+                        value,
+                    });
                 }
             }
 
             let add_element = |header: IfrStatementHeader, selectable: bool, editable: bool, list: bool| {
+                // Grayed out statements cannot be selected, read only and locked statements cannot be changed
+                let grayout = statement.Attribute & HII_DISPLAY_GRAYOUT != 0;
+                let read_only = statement.Attribute & (HII_DISPLAY_READONLY | HII_DISPLAY_LOCK) != 0;
+                let selectable = selectable && ! grayout;
+                let editable = editable && ! grayout && ! read_only;
                 let value = unsafe {
                     statement.CurrentValue.Value.to_enum(statement.CurrentValue.Kind)
                 };
@@ -567,20 +1531,56 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     }
                     Some(buffer)
                 };
-                if statement_ptr == form.HighLightedStatement || (selected == !0 && selectable) {
+                if (statement_ptr == form.HighLightedStatement || selected == !0) && selectable {
                     selected = elements.len();
                     debugln!("selected {}", selected);
                 }
+                let (is_password, is_string) = match statement.OpCode() {
+                    Some(op) => match op.OpCode {
+                        IfrOpCode::Password => (true, false),
+                        IfrOpCode::String => (false, true),
+                        _ => (false, false),
+                    },
+                    None => (false, false),
+                };
+                // Numerics with options are edited like one of questions
+                let numeric = if options.is_empty() {
+                    statement.OpCode().and_then(numeric_info)
+                } else {
+                    None
+                };
+                let date_time = statement.OpCode().and_then(date_time_visible);
+                let text_two = match statement.OpCode() {
+                    Some(op) => match op.OpCode {
+                        IfrOpCode::Text => unsafe { op.cast::<IfrText>() }.and_then(|text| {
+                            string(text.TextTwo).ok()
+                        }),
+                        _ => None,
+                    },
+                    None => None,
+                };
                 elements.push(Element {
                     statement_ptr,
                     prompt: string(header.Prompt).unwrap_or(String::new()),
                     help: string(header.Help).unwrap_or(String::new()),
+                    image_id: statement.ImageId,
+                    rendered: None,
+                    rendered_size: 0.0,
+                    image: None,
                     value,
                     options,
                     selectable,
                     editable,
+                    grayout,
+                    read_only,
                     list,
                     list_i: 0,
+                    password: is_password,
+                    string: is_string,
+                    numeric,
+                    date_time,
+                    text_two,
+                    depth,
                     buffer_opt,
                 });
             };
@@ -606,6 +1606,18 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     IfrOpCode::OneOf => if let Some(one_of) = unsafe { cast!(IfrOneOf) } {
                         add_element(one_of.Question.Header, true, true, false);
                     },
+                    IfrOpCode::Password => if let Some(password) = unsafe { cast!(IfrPassword) } {
+                        add_element(password.Question.Header, true, true, false);
+                    },
+                    IfrOpCode::String => if let Some(string) = unsafe { cast!(IfrString) } {
+                        add_element(string.Question.Header, true, true, false);
+                    },
+                    IfrOpCode::Date => if let Some(date) = unsafe { cast!(IfrDate) } {
+                        add_element(date.Question.Header, true, true, false);
+                    },
+                    IfrOpCode::Time => if let Some(time) = unsafe { cast!(IfrTime) } {
+                        add_element(time.Question.Header, true, true, false);
+                    },
                     IfrOpCode::OrderedList => if let Some(ordered_list) = unsafe { cast!(IfrOrderedList) } {
                         add_element(ordered_list.Question.Header, true, true, true);
                     },
@@ -615,294 +1627,427 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     IfrOpCode::Subtitle => if let Some(subtitle) = unsafe { cast!(IfrSubtitle) } {
                         add_element(subtitle.Statement, false, false, false);
                     },
+                    // Text has no question, interactive text is compiled to an action instead
+                    IfrOpCode::Text => if let Some(text) = unsafe { cast!(IfrText) } {
+                        add_element(text.Statement, false, false, false);
+                    },
                     _ => ()
                 }
             }
         }
 
         let title_opt = string(form.FormTitle).ok();
+        // Title image fitted to the title font size of each output, done once per size
+        let mut title_images: Vec<(u32, Option<Image>)> = Vec::new();
         let mut element_start = 0;
         'display: loop {
-            display.set(background_color);
+            // Every output shows the same state at its own resolution and scale, the first one
+            // is used for pointer input and paging
+            let mut primary_opt = None;
+            let mut zoom_limits = Vec::with_capacity(displays.len());
+            for (output_i, mut display) in displays.iter_mut().enumerate() {
+                // The resolution, scale and theme can change with hotkeys
+                let (display_w, display_h) = (display.width(), display.height());
+                let scale = display_scale(display);
+
+                // Style {
+                let theme = theme();
+
+                let background_color = theme.background_color;
+                let highlight_color = theme.highlight_color;
+                let outline_color = theme.outline_color;
+                let text_color = theme.text_color;
+                let grayout_color = theme.grayout_color;
+                let scrollbar_color = theme.scrollbar_color;
+                let highlight_text_color = theme.highlight_text_color;
+
+                let padding_lr = scaled(theme.padding_lr, scale);
+                let padding_tb = scaled(theme.padding_tb, scale);
+
+                let margin_lr = scaled(theme.margin_lr, scale);
+                let margin_tb = scaled(theme.margin_tb, scale);
+
+                let indent = scaled(theme.indent, scale);
+
+                let scrollbar_w = scaled(theme.scrollbar_w, scale);
+
+                let rect_radius = 4; //TODO: does not scale due to hardcoded checkbox image!
+
+                let title_font_size = scaled(theme.title_font_size, scale) as f32;
+                let font_size = scaled(theme.font_size, scale) as f32; // (display_h as f32) / 26.0
+                let help_font_size = scaled(theme.help_font_size, scale) as f32;
+                // } Style
+
+                let title_image_size = title_font_size as u32;
+                if ! title_images.iter().any(|cached| cached.0 == title_image_size) {
+                    title_images.push((title_image_size, hii_image(form.HiiHandle, form.ImageId).map(|image| {
+                        fit_image(image, title_image_size)
+                    })));
+                }
+                let title_image_opt = title_images.iter()
+                    .find(|cached| cached.0 == title_image_size)
+                    .and_then(|cached| cached.1.as_ref());
 
-            let draw_pretty_box = |display: &mut Display, x: i32, y: i32, w: u32, h: u32, highlighted: bool| {
-                let checkbox = if highlighted {
-                    // Center
-                    display.rect(
-                        x - padding_lr,
-                        y - padding_tb + rect_radius,
-                        w + padding_lr as u32 * 2,
-                        h + (padding_tb - rect_radius) as u32 * 2,
-                        highlight_color
-                    );
+                display.set(background_color);
 
-                    // Top middle
-                    display.rect(
-                        x - padding_lr + rect_radius,
-                        y - padding_tb,
-                        w + (padding_lr - rect_radius) as u32 * 2,
-                        rect_radius as u32,
-                        highlight_color,
-                    );
+                let draw_pretty_box = |display: &mut Display, x: i32, y: i32, w: u32, h: u32, highlighted: bool| {
+                    let checkbox = if highlighted {
+                        // Center
+                        display.rect(
+                            x - padding_lr,
+                            y - padding_tb + rect_radius,
+                            w + padding_lr as u32 * 2,
+                            h + (padding_tb - rect_radius) as u32 * 2,
+                            highlight_color
+                        );
 
-                    // Bottom middle
-                    display.rect(
-                        x - padding_lr + rect_radius,
-                        y + h as i32 + padding_tb - rect_radius,
-                        w + (padding_lr - rect_radius) as u32 * 2,
-                        rect_radius as u32,
-                        highlight_color,
-                    );
+                        // Top middle
+                        display.rect(
+                            x - padding_lr + rect_radius,
+                            y - padding_tb,
+                            w + (padding_lr - rect_radius) as u32 * 2,
+                            rect_radius as u32,
+                            highlight_color,
+                        );
 
-                    checkbox_checked
-                } else {
-                    // Top middle
-                    display.rect(
-                        x - padding_lr + rect_radius,
-                        y - padding_tb,
-                        w + (padding_lr - rect_radius) as u32 * 2,
-                        2,
-                        outline_color
+                        // Bottom middle
+                        display.rect(
+                            x - padding_lr + rect_radius,
+                            y + h as i32 + padding_tb - rect_radius,
+                            w + (padding_lr - rect_radius) as u32 * 2,
+                            rect_radius as u32,
+                            highlight_color,
+                        );
+
+                        checkbox_checked
+                    } else {
+                        // Top middle
+                        display.rect(
+                            x - padding_lr + rect_radius,
+                            y - padding_tb,
+                            w + (padding_lr - rect_radius) as u32 * 2,
+                            2,
+                            outline_color
+                        );
+
+                        // Bottom middle
+                        display.rect(
+                            x - padding_lr + rect_radius,
+                            y + h as i32 + padding_tb - 2,
+                            w + (padding_lr - rect_radius) as u32 * 2,
+                            2,
+                            outline_color
+                        );
+
+                        // Left middle
+                        display.rect(
+                            x - padding_lr,
+                            y - padding_tb + rect_radius,
+                            2,
+                            h + (padding_tb - rect_radius) as u32 * 2,
+                            outline_color
+                        );
+
+                        // Right middle
+                        display.rect(
+                            x + w as i32 + padding_lr - 2,
+                            y - padding_tb + rect_radius,
+                            2,
+                            h + (padding_tb - rect_radius) as u32 * 2,
+                            outline_color
+                        );
+
+                        checkbox_unchecked
+                    };
+
+                    // Top left
+                    checkbox.roi(
+                        0,
+                        0,
+                        rect_radius as u32,
+                        rect_radius as u32
+                    ).draw(
+                        display,
+                        x - padding_lr,
+                        y - padding_tb
                     );
 
-                    // Bottom middle
-                    display.rect(
-                        x - padding_lr + rect_radius,
-                        y + h as i32 + padding_tb - 2,
-                        w + (padding_lr - rect_radius) as u32 * 2,
-                        2,
-                        outline_color
+                    // Top right
+                    checkbox.roi(
+                        checkbox.width() - rect_radius as u32,
+                        0,
+                        rect_radius as u32,
+                        rect_radius as u32
+                    ).draw(
+                        display,
+                        x + w as i32 + padding_lr - rect_radius,
+                        y - padding_tb
                     );
 
-                    // Left middle
-                    display.rect(
+                    // Bottom left
+                    checkbox.roi(
+                        0,
+                        checkbox.height() - rect_radius as u32,
+                        rect_radius as u32,
+                        rect_radius as u32
+                    ).draw(
+                        display,
                         x - padding_lr,
-                        y - padding_tb + rect_radius,
-                        2,
-                        h + (padding_tb - rect_radius) as u32 * 2,
-                        outline_color
+                        y + h as i32 + padding_tb - rect_radius
                     );
 
-                    // Right middle
-                    display.rect(
-                        x + w as i32 + padding_lr - 2,
-                        y - padding_tb + rect_radius,
-                        2,
-                        h + (padding_tb - rect_radius) as u32 * 2,
-                        outline_color
+                    // Bottom right
+                    checkbox.roi(
+                        checkbox.width() - rect_radius as u32,
+                        checkbox.height() - rect_radius as u32,
+                        rect_radius as u32,
+                        rect_radius as u32
+                    ).draw(
+                        display,
+                        x + w as i32 + padding_lr - rect_radius,
+                        y + h as i32 + padding_tb - rect_radius
                     );
-
-                    checkbox_unchecked
                 };
 
-                // Top left
-                checkbox.roi(
-                    0,
-                    0,
-                    rect_radius as u32,
-                    rect_radius as u32
-                ).draw(
-                    display,
-                    x - padding_lr,
-                    y - padding_tb
-                );
-
-                // Top right
-                checkbox.roi(
-                    checkbox.width() - rect_radius as u32,
-                    0,
-                    rect_radius as u32,
-                    rect_radius as u32
-                ).draw(
-                    display,
-                    x + w as i32 + padding_lr - rect_radius,
-                    y - padding_tb
-                );
-
-                // Bottom left
-                checkbox.roi(
-                    0,
-                    checkbox.height() - rect_radius as u32,
-                    rect_radius as u32,
-                    rect_radius as u32
-                ).draw(
-                    display,
-                    x - padding_lr,
-                    y + h as i32 + padding_tb - rect_radius
-                );
-
-                // Bottom right
-                checkbox.roi(
-                    checkbox.width() - rect_radius as u32,
-                    checkbox.height() - rect_radius as u32,
-                    rect_radius as u32,
-                    rect_radius as u32
-                ).draw(
-                    display,
-                    x + w as i32 + padding_lr - rect_radius,
-                    y + h as i32 + padding_tb - rect_radius
-                );
-            };
-
-            let draw_text_box = |display: &mut Display, x: i32, y: i32, rendered: &Text, pretty_box: bool, highlighted: bool| {
-                if pretty_box {
-                    draw_pretty_box(display, x, y, rendered.width(), rendered.height(), highlighted);
-                }
-                let text_color = if highlighted {
-                    highlight_text_color
-                } else {
-                    text_color
+                let draw_text_box = |display: &mut Display, x: i32, y: i32, rendered: &Text, pretty_box: bool, highlighted: bool| {
+                    if pretty_box {
+                        draw_pretty_box(display, x, y, rendered.width(), rendered.height(), highlighted);
+                    }
+                    let text_color = if highlighted {
+                        highlight_text_color
+                    } else {
+                        text_color
+                    };
+                    rendered.draw(display, x, y, text_color);
                 };
-                rendered.draw(display, x, y, text_color);
-            };
 
-            let draw_check_box = |display: &mut Display, x: i32, y: i32, value: bool| -> i32 {
-                let checkbox = if value {
-                    checkbox_checked
-                } else {
-                    checkbox_unchecked
+                let draw_check_box = |display: &mut Display, x: i32, y: i32, value: bool| -> i32 {
+                    let checkbox = if value {
+                        checkbox_checked
+                    } else {
+                        checkbox_unchecked
+                    };
+                    checkbox.draw(display, x, y);
+                    checkbox.height() as i32
                 };
-                checkbox.draw(display, x, y);
-                checkbox.height() as i32
-            };
 
-            let draw_value_box = |display: &mut Display, x: i32, y: i32, value: &IfrTypeValueEnum, highlighted: bool| -> i32 {
-                //TODO: Do not format in drawing loop
-                let value_string = match value {
-                    IfrTypeValueEnum::U8(value) => format!("{}", value),
-                    IfrTypeValueEnum::U16(value) => format!("{}", value),
-                    IfrTypeValueEnum::U32(value) => format!("{}", value),
-                    IfrTypeValueEnum::U64(value) => format!("{}", value),
-                    IfrTypeValueEnum::Bool(value) => return draw_check_box(display, x, y, *value),
-                    other => format!("{:?}", other),
+                // Values change while a form is shown, so they are not cached
+                let draw_string_box = |display: &mut Display, x: i32, y: i32, string: &str, highlighted: bool| -> i32 {
+                    let rendered = font.render(string, font_size);
+                    draw_text_box(display, x, y, &rendered, true, highlighted);
+                    rendered.height() as i32
                 };
 
-                // TODO: Do not render in drawing loop
-                let rendered = font.render(&value_string, font_size);
-                draw_text_box(display, x, y, &rendered, true, highlighted);
-                rendered.height() as i32
-            };
+                let draw_value_box = |display: &mut Display, x: i32, y: i32, value: &IfrTypeValueEnum, highlighted: bool| -> i32 {
+                    //TODO: Do not format in drawing loop
+                    let value_string = match value {
+                        IfrTypeValueEnum::U8(value) => format!("{}", value),
+                        IfrTypeValueEnum::U16(value) => format!("{}", value),
+                        IfrTypeValueEnum::U32(value) => format!("{}", value),
+                        IfrTypeValueEnum::U64(value) => format!("{}", value),
+                        IfrTypeValueEnum::Bool(value) => return draw_check_box(display, x, y, *value),
+                        IfrTypeValueEnum::String(value) => string(*value).unwrap_or(String::new()),
+                        other => format!("{:?}", other),
+                    };
 
-            let draw_options_box = |display: &mut Display, x: i32, mut y: i32, element: &Element| {
-                let mut w = 0;
-                for option in element.options.iter() {
-                    w = cmp::max(w, option.prompt.width());
-                }
+                    draw_string_box(display, x, y, &value_string, highlighted)
+                };
 
-                let start_y = y;
-                for (i, option) in element.options.iter().enumerate() {
-                    let highlighted = i == element.list_i;
-                    if highlighted && editing {
-                        draw_pretty_box(display, x, y, w, option.prompt.height(), true);
-                    }
-                    let text_color = if highlighted && editing {
-                        highlight_text_color
+                let draw_line_editor = |display: &mut Display, x: i32, y: i32, editor: &LineEditor, masked: bool| {
+                    let chars: String = if masked {
+                        editor.chars().iter().map(|_| '•').collect()
                     } else {
-                        text_color
+                        editor.string()
                     };
-                    option.prompt.draw(display, x, y, text_color);
-                    y += option.prompt.height() as i32 + margin_tb;
-                }
-                if y > start_y {
-                    draw_pretty_box(display, x, start_y, w, (y - start_y - margin_tb) as u32, false);
-                }
-
-                y
-            };
+                    let before_cursor: String = chars.chars().take(editor.cursor()).collect();
 
-            let mut y = margin_tb;
-            let mut bottom_y = display_h as i32;
+                    let rendered = font.render(&chars, font_size);
+                    let cursor_x = x + font.render(&before_cursor, font_size).width() as i32;
+                    let cursor_w = cmp::max(scaled(1, scale) as u32, 1);
+                    let h = cmp::max(rendered.height(), font_size as u32);
 
-            let (editing_list, editing_value) = elements.get(selected)
-                .map(|e| (e.list, e.options.is_empty()))
-                .unwrap_or((false, false));
+                    draw_pretty_box(display, x, y, rendered.width() + cursor_w, h, true);
+                    rendered.draw(display, x, y, highlight_text_color);
+                    display.rect(cursor_x, y, cursor_w, h, highlight_text_color);
+                };
 
-            // Draw header
-            if let Some(ref title) = title_opt {
-                // TODO: Do not render in drawing loop
-                let rendered = font.render(&title, title_font_size);
-                let x = (display_w as i32 - rendered.width() as i32) / 2;
-                draw_text_box(&mut display, x, y, &rendered, false, false);
-                y += rendered.height() as i32 + margin_tb;
-            }
+                let draw_date_time_editor = |display: &mut Display, x: i32, y: i32, editor: &DateTimeEditor| {
+                    let separator = text(font, editor.separator(), font_size);
+                    let segments: Vec<(Text, bool)> = editor.segments().into_iter().map(|(string, selected)| {
+                        (font.render(&string, font_size), selected)
+                    }).collect();
 
-            display.rect(
-                0,
-                y,
-                display_w,
-                1,
-                Color::rgb(0xac, 0xac, 0xac)
-            );
-            y += margin_tb * 2;
-
-            // Draw footer
-            {
-                let mut i = 0;
-                let mut render_hotkey_help = |help: &str| {
-                    let rendered = font.render(help, help_font_size);
-                    let x = match i % 3 {
-                        0 => {
-                            bottom_y -= rendered.height() as i32 + margin_tb;
-                            (display_w as i32) * 2 / 3 + margin_lr
-                        },
-                        1 =>  {
-                            (display_w as i32) / 3 + margin_lr
-                        },
-                        _ => {
-                            margin_lr
+                    let mut w = 0;
+                    for (i, (rendered, _)) in segments.iter().enumerate() {
+                        if i > 0 {
+                            w += separator.width();
                         }
-                    };
-                    draw_text_box(&mut display, x, bottom_y, &rendered, false, false);
-                    i += 1;
+                        w += rendered.width();
+                    }
+                    let h = font_size as u32;
+                    draw_pretty_box(display, x, y, w, h, false);
+
+                    let mut x = x;
+                    for (i, (rendered, selected)) in segments.iter().enumerate() {
+                        if i > 0 {
+                            separator.draw(display, x, y, text_color);
+                            x += separator.width() as i32;
+                        }
+                        if *selected {
+                            draw_pretty_box(display, x, y, rendered.width(), h, true);
+                            rendered.draw(display, x, y, highlight_text_color);
+                        } else {
+                            rendered.draw(display, x, y, text_color);
+                        }
+                        x += rendered.width() as i32;
+                    }
                 };
 
-                if editing {
-                    render_hotkey_help("Esc=Discard Changes");
-                } else if form.FormId == FRONT_PAGE_FORM_ID {
-                    render_hotkey_help("");
-                } else {
-                    render_hotkey_help("Esc=Exit");
-                }
-                if selected == !0 {
-                    render_hotkey_help("");
-                } else if editing {
-                    render_hotkey_help("Enter=Save Changes");
-                } else {
-                    render_hotkey_help("Enter=Select Entry");
-                }
-                if selected == !0 {
-                    render_hotkey_help("");
-                } else if ! editing || ! editing_value {
-                    render_hotkey_help("↑↓=Move Highlight");
-                }
+                let draw_options_box = |display: &mut Display, x: i32, mut y: i32, element: &Element| {
+                    let mut image_w = 0;
+                    for option in element.options.iter() {
+                        if let Some(ref image) = option.image {
+                            image_w = cmp::max(image_w, image.width() + margin_lr as u32);
+                        }
+                    }
 
-                if editing {
-                    if editing_list {
-                        render_hotkey_help("PgDn=Move Selection Down");
-                        render_hotkey_help("");
-                        render_hotkey_help("PgUp=Move Selection Up");
+                    let mut w = 0;
+                    for option in element.options.iter() {
+                        if let Some(ref prompt) = option.rendered {
+                            w = cmp::max(w, image_w + prompt.width());
+                        }
                     }
-                } else {
-                    for hotkey_help in hotkey_helps.iter() {
-                        render_hotkey_help(&hotkey_help);
+
+                    let start_y = y;
+                    for (i, option) in element.options.iter().enumerate() {
+                        let prompt = match option.rendered {
+                            Some(ref prompt) => prompt,
+                            None => continue,
+                        };
+                        let highlighted = i == element.list_i;
+                        if highlighted && editing {
+                            draw_pretty_box(display, x, y, w, prompt.height(), true);
+                        }
+                        let text_color = if highlighted && editing {
+                            highlight_text_color
+                        } else {
+                            text_color
+                        };
+                        if let Some(ref image) = option.image {
+                            image.draw(display, x, y + (prompt.height() as i32 - image.height() as i32) / 2);
+                        }
+                        prompt.draw(display, x + image_w as i32, y, text_color);
+                        y += prompt.height() as i32 + margin_tb;
+                    }
+                    if y > start_y {
+                        draw_pretty_box(display, x, start_y, w, (y - start_y - margin_tb) as u32, false);
+                    }
+
+                    y
+                };
+
+                let mut y = margin_tb;
+                let mut bottom_y = display_h as i32;
+
+                let (editing_list, editing_value) = elements.get(selected)
+                    .map(|e| (e.list, e.options.is_empty()))
+                    .unwrap_or((false, false));
+
+                // Draw header
+                if let Some(ref title) = title_opt {
+                    let rendered = text(font, &title, title_font_size);
+                    let image_w = title_image_opt.as_ref().map_or(0, |image| image.width() as i32 + margin_lr);
+                    let mut x = (display_w as i32 - image_w - rendered.width() as i32) / 2;
+                    if let Some(ref image) = title_image_opt {
+                        image.draw(display, x, y + (rendered.height() as i32 - image.height() as i32) / 2);
+                        x += image_w;
                     }
+                    draw_text_box(&mut display, x, y, &rendered, false, false);
+                    y += rendered.height() as i32 + margin_tb;
                 }
 
-                bottom_y -= margin_tb * 3 / 2;
                 display.rect(
                     0,
-                    bottom_y,
+                    y,
                     display_w,
                     1,
                     Color::rgb(0xac, 0xac, 0xac)
                 );
+                y += margin_tb * 2;
+
+                // Draw footer
+                {
+                    let mut i = 0;
+                    let mut render_hotkey_help = |help: &str| {
+                        let rendered = text(font, help, help_font_size);
+                        let x = match i % 3 {
+                            0 => {
+                                bottom_y -= rendered.height() as i32 + margin_tb;
+                                (display_w as i32) * 2 / 3 + margin_lr
+                            },
+                            1 =>  {
+                                (display_w as i32) / 3 + margin_lr
+                            },
+                            _ => {
+                                margin_lr
+                            }
+                        };
+                        draw_text_box(&mut display, x, bottom_y, &rendered, false, false);
+                        i += 1;
+                    };
+
+                    if editing {
+                        render_hotkey_help("Esc=Discard Changes");
+                    } else if form.FormId == FRONT_PAGE_FORM_ID {
+                        render_hotkey_help("");
+                    } else {
+                        render_hotkey_help("Esc=Exit");
+                    }
+                    if selected == !0 {
+                        render_hotkey_help("");
+                    } else if editing {
+                        render_hotkey_help("Enter=Save Changes");
+                    } else {
+                        render_hotkey_help("Enter=Select Entry");
+                    }
+                    if selected == !0 {
+                        render_hotkey_help("");
+                    } else if ! editing || ! editing_value {
+                        render_hotkey_help("↑↓=Move Highlight");
+                    } else if let Some(Editor::Numeric(_)) = editor_opt {
+                        render_hotkey_help("↑↓ +-=Adjust Value");
+                    } else if let Some(Editor::DateTime(_)) = editor_opt {
+                        render_hotkey_help("←→=Select Field  ↑↓=Adjust Value");
+                    }
+
+                    if editing {
+                        if editing_list {
+                            render_hotkey_help("PgDn=Move Selection Down");
+                            render_hotkey_help("");
+                            render_hotkey_help("PgUp=Move Selection Up");
+                        }
+                    } else {
+                        for hotkey_help in hotkey_helps.iter() {
+                            render_hotkey_help(&hotkey_help);
+                        }
+                        render_hotkey_help("Ctrl+A=High Contrast  Ctrl+±=Zoom");
+                        render_hotkey_help("Ctrl+R=Resolution");
+                    }
+
+                    bottom_y -= margin_tb * 3 / 2;
+                    display.rect(
+                        0,
+                        bottom_y,
+                        display_w,
+                        1,
+                        Color::rgb(0xac, 0xac, 0xac)
+                    );
 
-                if let Some(element) = elements.get(selected) {
-                    if !element.help.trim().is_empty() {
-                        let rendered = font.render(&element.help, help_font_size);
+                    if let Some(ref error) = error_opt {
+                        let rendered = text(font, error, help_font_size);
                         let x = (display_w as i32 - rendered.width() as i32) / 2;
                         bottom_y -= rendered.height() as i32 + margin_tb;
-                        draw_text_box(&mut display, x, bottom_y, &rendered, false, false);
+                        rendered.draw(display, x, bottom_y, highlight_color);
 
                         bottom_y -= margin_tb * 3 / 2;
                         display.rect(
@@ -913,83 +2058,301 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                             Color::rgb(0xac, 0xac, 0xac)
                         );
                     }
+
+                    if let Some(element) = elements.get(selected) {
+                        let help = match editor_opt {
+                            Some(Editor::Password(ref password)) => password.prompt(),
+                            _ => &element.help,
+                        };
+                        if !help.trim().is_empty() {
+                            let rendered = text(font, help, help_font_size);
+                            let x = (display_w as i32 - rendered.width() as i32) / 2;
+                            bottom_y -= rendered.height() as i32 + margin_tb;
+                            draw_text_box(&mut display, x, bottom_y, &rendered, false, false);
+
+                            bottom_y -= margin_tb * 3 / 2;
+                            display.rect(
+                                0,
+                                bottom_y,
+                                display_w,
+                                1,
+                                Color::rgb(0xac, 0xac, 0xac)
+                            );
+                        }
+                    }
                 }
-            }
 
-            // Draw body
-            let max_form_elements = ((bottom_y - y) / (font_size as i32 + margin_tb)) as usize;
+                // Draw body
+                // The header and footer can take up the whole display when zoomed in
+                let body_h = cmp::max(bottom_y - y, 0);
+                let row_h = cmp::max(1, font_size as i32 + margin_tb);
+                let max_form_elements = (body_h / row_h) as usize;
 
-            if element_start > 0 {
-                // Draw up arrow to indicate more items above
-                let arrow = font.render("↑", help_font_size);
-                draw_text_box(&mut display, (display_w - arrow.width()) as i32 - margin_lr, y, &arrow, false, false);
-            }
+                // Everything but the body grows with the scale, so this is the largest scale
+                // that leaves room for one row
+                let fixed_h = display_h as i32 - body_h + row_h;
+                zoom_limits.push((display.scale(), scale * display_h as f32 / fixed_h as f32));
 
-            for i in element_start..(element_start + max_form_elements) {
-                if let Some(element) = elements.get(i) {
-                    let highlighted = i == selected;
-                    let h = {
-                        // TODO: Do not render in drawing loop
-                        let rendered = font.render(&element.prompt, font_size);
-                        draw_text_box(&mut display, margin_lr, y, &rendered, highlighted && ! editing, highlighted && ! editing);
-                        rendered.height() as i32
-                    };
+                // Outputs with fewer rows scroll on their own to keep the selection shown
+                let element_start = if output_i == 0 || selected == !0 {
+                    element_start
+                } else {
+                    visible_start(selected, element_start, max_form_elements)
+                };
+
+                if elements.len() > max_form_elements {
+                    // Draw a scrollbar sized by the portion of the elements shown
+                    let track_x = display_w as i32 - margin_lr / 2 - scrollbar_w;
+                    let track_h = bottom_y - margin_tb * 2 - y;
+                    let thumb_h = cmp::max(
+                        track_h * max_form_elements as i32 / elements.len() as i32,
+                        scrollbar_w * 2
+                    );
+                    let thumb_y = y + (track_h - thumb_h) * element_start as i32
+                        / (elements.len() - max_form_elements) as i32;
+                    display.rect(track_x, y, scrollbar_w as u32, track_h as u32, scrollbar_color);
+                    display.rect(track_x, thumb_y, scrollbar_w as u32, thumb_h as u32, outline_color);
+                }
+
+                // Only the shown elements are laid out
+                let element_end = cmp::min(element_start + max_form_elements, elements.len());
+                for element in elements[cmp::min(element_start, element_end)..element_end].iter_mut() {
+                    element.layout(font, font_size, form.HiiHandle);
+                }
+
+                // Element index, top, and bottom of each row, for hit testing pointer clicks
+                let mut element_rows = Vec::new();
+                for i in element_start..(element_start + max_form_elements) {
+                    if let Some(element) = elements.get(i) {
+                        let highlighted = i == selected;
+                        let row_y = y;
+                        let h = if let Some(ref rendered) = element.rendered {
+                            let mut x = margin_lr + element.depth as i32 * indent;
+                            if let Some(ref image) = element.image {
+                                image.draw(display, x, y + (rendered.height() as i32 - image.height() as i32) / 2);
+                                x += image.width() as i32 + margin_lr;
+                            }
+                            draw_text_box(&mut display, x, y, rendered, highlighted && ! editing, highlighted && ! editing);
+                            if let Some((start, end)) = search_match(&element.prompt, &search) {
+                                // Underline the part of the prompt matching the search
+                                let before = font.render(&element.prompt[..start], font_size);
+                                let matched = font.render(&element.prompt[start..end], font_size);
+                                display.rect(
+                                    x + before.width() as i32,
+                                    y + rendered.height() as i32 - scaled(1, scale),
+                                    matched.width(),
+                                    scaled(2, scale) as u32,
+                                    if highlighted && ! editing { highlight_text_color } else { highlight_color }
+                                );
+                            }
+                            rendered.height() as i32
+                        } else {
+                            font_size as i32
+                        };
+
+                        let x = display_w as i32 / 2;
+                        if element.list {
+                            y = draw_options_box(&mut display, x, y, element);
+                            y -= h + margin_tb;
+                        } else if let Some(option) = element.options.iter().find(|o| o.value == element.value) {
+                            if let Some(ref prompt) = option.rendered {
+                                let mut x = x;
+                                if let Some(ref image) = option.image {
+                                    image.draw(display, x - padding_lr, y + (prompt.height() as i32 - image.height() as i32) / 2);
+                                    x += image.width() as i32 + margin_lr;
+                                }
+                                draw_text_box(&mut display, x, y, prompt, true, highlighted && editing);
+                            }
+                        } else if let (true, Some(editor)) = (highlighted && editing, &editor_opt) {
+                            match editor {
+                                Editor::Password(password) => draw_line_editor(&mut display, x, y, &password.editor, true),
+                                Editor::String(editor) => draw_line_editor(&mut display, x, y, editor, false),
+                                Editor::Numeric(editor) => {
+                                    draw_string_box(&mut display, x, y, &editor.string(), true);
+                                },
+                                Editor::DateTime(editor) => draw_date_time_editor(&mut display, x, y, editor),
+                            }
+                        } else if element.password {
+                            // Password values are never shown
+                        } else if let (Some(numeric), Some(value)) = (element.numeric, numeric_value(&element.value)) {
+                            draw_string_box(&mut display, x, y, &numeric.format(value), highlighted && editing);
+                        } else if let Some(editor) = element.date_time.and_then(|visible| date_time_editor(&element.value, visible)) {
+                            draw_string_box(&mut display, x, y, &editor.string(), false);
+                        } else if let Some(ref text_two) = element.text_two {
+                            if ! text_two.trim().is_empty() {
+                                let rendered = text(font, text_two, font_size);
+                                draw_text_box(&mut display, x, y, &rendered, false, false);
+                            }
+                        } else if element.editable {
+                            draw_value_box(&mut display, x, y, &element.value, highlighted && editing);
+                        }
 
-                    let x = display_w as i32 / 2;
-                    if element.list {
-                        y = draw_options_box(&mut display, x, y, element);
-                        y -= h + margin_tb;
-                    } else if let Some(option) = element.options.iter().find(|o| o.value == element.value) {
-                        draw_text_box(&mut display, x, y, &option.prompt, true, highlighted && editing);
-                    } else if element.editable {
-                        draw_value_box(&mut display, x, y, &element.value, highlighted && editing);
+                        if element.grayout {
+                            // Dim the whole row, including its value
+                            display.rect(
+                                0,
+                                row_y - padding_tb,
+                                display_w,
+                                (y + h + padding_tb * 2 - row_y) as u32,
+                                grayout_color
+                            );
+                        }
+
+                        element_rows.push((i, row_y - padding_tb, y + h + padding_tb));
+
+                        y += h + margin_tb;
                     }
+                }
 
-                    y += h + margin_tb;
+                if output_i == 0 {
+                    pointer.draw(display, scale);
                 }
-            }
 
-            if elements.len() > max_form_elements && element_start < elements.len() - max_form_elements {
-                // Draw down arrow to indicate more items below
-                let arrow = font.render("↓", help_font_size);
-                draw_text_box(&mut display, (display_w - arrow.width()) as i32 - margin_lr, bottom_y - arrow.height() as i32 - margin_tb * 2, &arrow, false, false);
+                // Only blit what changed, so moving the highlight does not copy the whole frame
+                display.sync_damage();
+
+                if output_i == 0 {
+                    primary_opt = Some((display_w, display_h, scale, display.rotation(), max_form_elements, element_rows));
+                }
             }
 
-            display.sync();
+            let (display_w, display_h, scale, rotation, max_form_elements, element_rows) = match primary_opt {
+                Some(some) => some,
+                None => return Err(Error::NotFound),
+            };
 
-            let signaled = wait_for_events(form)?;
+            let signaled = wait_for_events(form, pointer)?;
             if signaled == EventType::Driver {
                 user_input.Action = BROWSER_ACTION_NONE;
                 break 'render;
             }
 
+            // Pointer input is turned into key presses, handled before the keyboard
+            let mut pointer_keys = Vec::new();
+            for event in pointer.poll(display_w, display_h, scale, rotation) {
+                match event {
+                    PointerEvent::Move => (),
+                    PointerEvent::Click(x, y) => {
+                        let row_opt = element_rows.iter().find(|row| y >= row.1 && y < row.2);
+                        debugln!("click at {}, {}: {:?}", x, y, row_opt);
+                        if let Some(&(i, _, _)) = row_opt {
+                            if editing {
+                                if i == selected {
+                                    pointer_keys.push(Key::Enter);
+                                } else {
+                                    pointer_keys.push(Key::Escape);
+                                }
+                            } else if elements[i].selectable {
+                                selected = i;
+                                pointer_keys.push(Key::Enter);
+                            }
+                        }
+                    },
+                    PointerEvent::Scroll(rows) => if editing {
+                        pointer_keys.push(if rows < 0 { Key::Up } else { Key::Down });
+                    } else if elements.len() > max_form_elements {
+                        let max_start = elements.len() - max_form_elements;
+                        let start = cmp::min(cmp::max(element_start as i32 + rows, 0) as usize, max_start);
+                        let end = start + max_form_elements;
+                        if selected >= start && selected < end {
+                            element_start = start;
+                        } else if let Some(i) = (start..end).find(|&i| elements[i].selectable) {
+                            // Keep the selection visible
+                            element_start = start;
+                            selected = i;
+                        }
+                    },
+                }
+            }
+            pointer_keys.reverse();
+
             // Consume all queued key presses
             'input: loop {
-                let raw_key = match raw_key(false) {
-                    Ok(ok) => ok,
-                    Err(err) => match err {
-                        Error::NotReady => break 'input,
-                        _ => return Err(err),
+                let key = if let Some(key) = pointer_keys.pop() {
+                    key
+                } else {
+                    let (raw_key, control) = match raw_key_control(false) {
+                        Ok(ok) => ok,
+                        Err(err) => match err {
+                            Error::NotReady => break 'input,
+                            _ => return Err(err),
+                        }
+                    };
+
+                    if accessibility_key(&raw_key, control, &zoom_limits) || resolution_key(&raw_key, control, &mut displays[0]) {
+                        continue 'input;
                     }
-                };
 
-                if ! editing {
-                    for hotkey in form.HotKeyListHead.iter() {
-                        let key_data = unsafe { &*hotkey.KeyData };
-                        if key_data.ScanCode == raw_key.ScanCode && key_data.UnicodeChar == raw_key.UnicodeChar {
-                            debugln!("pressed {}", ffi::nstr(hotkey.HelpString));
-                            user_input.Action = hotkey.Action;
-                            user_input.DefaultId = hotkey.DefaultId;
-                            break 'render;
+                    if ! editing {
+                        for hotkey in form.HotKeyListHead.iter() {
+                            let key_data = unsafe { &*hotkey.KeyData };
+                            if key_data.ScanCode == raw_key.ScanCode && key_data.UnicodeChar == raw_key.UnicodeChar {
+                                debugln!("pressed {}", ffi::nstr(hotkey.HelpString));
+                                user_input.Action = hotkey.Action;
+                                user_input.DefaultId = hotkey.DefaultId;
+                                break 'render;
+                            }
                         }
                     }
-                }
 
-                let key = Key::from(raw_key);
+                    Key::from(raw_key)
+                };
                 debugln!("{:?}", key);
+                error_opt = None;
+
+                match key {
+                    Key::Character(_) => (),
+                    _ => search.clear(),
+                }
+
+                if let Some(ref mut editor) = editor_opt {
+                    if editor.key(&key) {
+                        error_opt = editor.error();
+                        continue 'input;
+                    }
+                }
+
                 match key {
+                    Key::Character(c) if ! editing && ! c.is_control() => {
+                        // Without a timer, every character starts a new search
+                        if search_timer_opt.as_ref().map_or(true, |timer| timer.expired()) {
+                            search.clear();
+                        }
+                        if let Some(ref timer) = search_timer_opt {
+                            timer.restart();
+                        }
+                        search.push(c);
+
+                        // A new search moves past the current selection, a longer search may stay on it
+                        let current = if selected == !0 { 0 } else { selected };
+                        let start = if search.chars().count() == 1 { current + 1 } else { current };
+                        let order: Vec<usize> = (start..elements.len()).chain(0..cmp::min(start, elements.len())).collect();
+
+                        // Prefer prompts starting with the search over prompts containing it
+                        let found_opt = order.iter().cloned().find(|&i| {
+                            elements[i].selectable && search_match(&elements[i].prompt, &search).map_or(false, |m| m.0 == 0)
+                        }).or_else(|| order.iter().cloned().find(|&i| {
+                            elements[i].selectable && search_match(&elements[i].prompt, &search).is_some()
+                        }));
+
+                        match found_opt {
+                            Some(i) => {
+                                selected = i;
+                                element_start = visible_start(selected, element_start, max_form_elements);
+                            },
+                            None => {
+                                // Ignore characters that do not match anything
+                                search.pop();
+                            }
+                        }
+                    },
                     Key::Enter => {
                         if let Some(element) = elements.get_mut(selected) {
+                            if element.read_only {
+                                debugln!("statement is read only");
+                                continue 'input;
+                            }
+
                             let mut checkbox = false;
                             {
                                 let statement = unsafe { &(*element.statement_ptr) };
@@ -1001,7 +2364,164 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                 }
                             }
 
-                            if checkbox {
+                            if element.password {
+                                let statement = unsafe { &(*element.statement_ptr) };
+                                if let Some(Editor::Password(mut password)) = editor_opt.take() {
+                                    editing = false;
+                                    match password.stage {
+                                        PasswordStage::Old => match password.check(form, statement).into_result() {
+                                            Ok(_) => {
+                                                password.stage = PasswordStage::New;
+                                                password.editor.clear();
+                                                editor_opt = Some(Editor::Password(password));
+                                                editing = true;
+                                            },
+                                            Err(err) => {
+                                                debugln!("password check failed: {:?}", err);
+                                                let message = form_error(form).unwrap_or_else(|| if err == Error::NotReady {
+                                                    "Incorrect password".to_string()
+                                                } else {
+                                                    format!("Failed to check password: {:?}", err)
+                                                });
+                                                alert(displays, font, "Error", &message)?;
+                                            }
+                                        },
+                                        PasswordStage::New => {
+                                            if ! password.editor.valid() {
+                                                alert(displays, font, "Error", &format!("Password must be at least {} characters", password.editor.min()))?;
+                                            } else {
+                                                zero_string(&mut password.new);
+                                                password.new = password.editor.string();
+                                                password.stage = PasswordStage::Confirm;
+                                            }
+                                            password.editor.clear();
+                                            editor_opt = Some(Editor::Password(password));
+                                            editing = true;
+                                        },
+                                        PasswordStage::Confirm => if password.confirmed() {
+                                            user_input.SelectedStatement = element.statement_ptr;
+                                            string_input_value(hii_string, form, statement, &password.new, &mut user_input.InputValue)?;
+                                            break 'render;
+                                        } else {
+                                            // Reset password state machine
+                                            let _ = password_check(form, statement, None);
+                                            alert(displays, font, "Error", "Passwords do not match")?;
+                                        },
+                                    }
+                                } else if element.editable {
+                                    let (min, max) = match statement.OpCode().and_then(|op| unsafe { op.cast::<IfrPassword>() }) {
+                                        Some(op) => (op.MinSize as usize, op.MaxSize as usize),
+                                        None => (0, 0),
+                                    };
+                                    let status = password_check(form, statement, None);
+                                    if status.0 == STATUS_NOT_AVAILABLE_YET {
+                                        debugln!("password not available yet");
+                                    } else {
+                                        let stage_opt = match status.into_result() {
+                                            // No password is set
+                                            Ok(_) => Some(PasswordStage::New),
+                                            Err(Error::Unsupported) => {
+                                                alert(displays, font, "Error", "Password is not supported")?;
+                                                None
+                                            },
+                                            // Password is set, old password must be checked
+                                            Err(_) => Some(PasswordStage::Old),
+                                        };
+                                        if let Some(stage) = stage_opt {
+                                            editor_opt = Some(Editor::Password(PasswordInput::new(stage, min, max)));
+                                            editing = true;
+                                        }
+                                    }
+                                }
+                            } else if element.string {
+                                let statement = unsafe { &(*element.statement_ptr) };
+                                if let Some(Editor::String(editor)) = editor_opt.take() {
+                                    if editor.valid() {
+                                        user_input.SelectedStatement = element.statement_ptr;
+                                        string_input_value(hii_string, form, statement, &editor.string(), &mut user_input.InputValue)?;
+                                        editing = false;
+                                        break 'render;
+                                    } else {
+                                        alert(displays, font, "Error", &format!("Value must be at least {} characters", editor.min()))?;
+                                        editor_opt = Some(Editor::String(editor));
+                                    }
+                                } else if element.editable {
+                                    let (min, max) = match statement.OpCode().and_then(|op| unsafe { op.cast::<IfrString>() }) {
+                                        Some(op) => (op.MinSize as usize, op.MaxSize as usize),
+                                        None => (0, 0),
+                                    };
+                                    let current = match element.value {
+                                        IfrTypeValueEnum::String(string_id) => string(string_id).unwrap_or(String::new()),
+                                        _ => String::new(),
+                                    };
+                                    editor_opt = Some(Editor::String(LineEditor::new(&current, min, max)));
+                                    editing = true;
+                                }
+                            } else if let (Some(numeric), Some(current)) = (element.numeric, numeric_value(&element.value)) {
+                                if let Some(Editor::Numeric(editor)) = editor_opt.take() {
+                                    match editor.value() {
+                                        Ok(value) => {
+                                            user_input.SelectedStatement = element.statement_ptr;
+                                            unsafe {
+                                                ptr::copy(
+                                                    &(*element.statement_ptr).CurrentValue,
+                                                    &mut user_input.InputValue,
+                                                    1
+                                                );
+                                            }
+
+                                            let (kind, value) = unsafe {
+                                                numeric_value_enum(&numeric, value).to_union()
+                                            };
+                                            user_input.InputValue.Kind = kind;
+                                            user_input.InputValue.Value = value;
+
+                                            editing = false;
+                                            break 'render;
+                                        },
+                                        Err(err) => {
+                                            alert(displays, font, "Error", &err)?;
+                                            editor_opt = Some(Editor::Numeric(editor));
+                                        }
+                                    }
+                                } else if element.editable {
+                                    editor_opt = Some(Editor::Numeric(NumericEditor::new(numeric, numeric.clamp(current))));
+                                    editing = true;
+                                }
+                            } else if let Some(visible) = element.date_time {
+                                if let Some(Editor::DateTime(editor)) = editor_opt.take() {
+                                    match editor.value() {
+                                        Ok(fields) => {
+                                            user_input.SelectedStatement = element.statement_ptr;
+                                            unsafe {
+                                                ptr::copy(
+                                                    &(*element.statement_ptr).CurrentValue,
+                                                    &mut user_input.InputValue,
+                                                    1
+                                                );
+                                            }
+
+                                            let (kind, value) = unsafe {
+                                                date_time_value_enum(editor.kind(), fields).to_union()
+                                            };
+                                            user_input.InputValue.Kind = kind;
+                                            user_input.InputValue.Value = value;
+
+                                            editing = false;
+                                            break 'render;
+                                        },
+                                        Err(err) => {
+                                            alert(displays, font, "Error", &err)?;
+                                            editor_opt = Some(Editor::DateTime(editor));
+                                        }
+                                    }
+                                } else if element.editable {
+                                    if let Some(editor) = date_time_editor(&element.value, visible) {
+                                        editor_opt = Some(Editor::DateTime(editor));
+                                        editing = true;
+                                    }
+                                }
+                            } else if checkbox {
                                 match element.value {
                                     IfrTypeValueEnum::Bool(b) => {
                                         user_input.SelectedStatement = element.statement_ptr;
@@ -1086,6 +2606,15 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     },
                     Key::Escape => {
                         if editing {
+                            if let Some(Editor::Password(password)) = editor_opt.take() {
+                                if password.stage != PasswordStage::Old {
+                                    if let Some(element) = elements.get(selected) {
+                                        // Reset password state machine
+                                        let statement = unsafe { &(*element.statement_ptr) };
+                                        let _ = password_check(form, statement, None);
+                                    }
+                                }
+                            }
                             editing = false;
                             break 'display;
                         } else if form.FormId != FRONT_PAGE_FORM_ID {
@@ -1115,28 +2644,13 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                 }
                             }
                         } else if selected != !0 {
-                            let start = selected;
-                            loop {
-                                if selected + 1 < elements.len() {
-                                    selected += 1;
-                                } else {
-                                    selected = 0;
-                                }
-                                if let Some(element) = elements.get(selected) {
-                                    if element.selectable {
-                                        break;
-                                    }
-                                }
-                                if selected == start {
-                                    break;
-                                }
-                            }
-                            if selected == 0 {
-                                // Handle wrapping
+                            let previous = selected;
+                            selected = step_selectable(elements.len(), selected, true, |i| elements[i].selectable);
+                            if selected < previous {
+                                // Handle wrapping, showing any rows above the selection
                                 element_start = 0;
-                            } else if selected - element_start >= max_form_elements {
-                                element_start = selected - max_form_elements + 1;
                             }
+                            element_start = visible_start(selected, element_start, max_form_elements);
                         }
                     },
                     Key::Up => {
@@ -1161,32 +2675,13 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                 }
                             }
                         } else if selected != !0 {
-                            let start = selected;
-                            loop {
-                                if selected > 0 {
-                                    selected -= 1;
-                                } else {
-                                    selected = cmp::max(elements.len(), 1) - 1;
-                                }
-                                if let Some(element) = elements.get(selected) {
-                                    if element.selectable {
-                                        break;
-                                    }
-                                }
-                                if selected == start {
-                                    break;
-                                }
-                            }
-                            if selected <= element_start {
-                                element_start = selected;
-                            } else if selected == elements.len() - 1 {
-                                // Handle wrapping
-                                element_start = if selected >= max_form_elements {
-                                    selected - max_form_elements + 1
-                                } else {
-                                    0
-                                };
+                            let previous = selected;
+                            selected = step_selectable(elements.len(), selected, false, |i| elements[i].selectable);
+                            if selected > previous {
+                                // Handle wrapping, showing any rows below the selection
+                                element_start = elements.len().saturating_sub(max_form_elements);
                             }
+                            element_start = visible_start(selected, element_start, max_form_elements);
                         }
                     },
                     Key::PageDown => {
@@ -1199,6 +2694,20 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     }
                                 }
                             }
+                        } else if selected != !0 && ! elements.is_empty() {
+                            // Move a page down, to the last selectable element on it
+                            let target = cmp::min(selected + cmp::max(max_form_elements, 1), elements.len() - 1);
+                            let next_opt = selectable_from(&elements, target, false)
+                                .filter(|&i| i > selected)
+                                .or_else(|| selectable_from(&elements, target, true));
+                            if let Some(next) = next_opt {
+                                selected = next;
+                                element_start = cmp::min(
+                                    element_start + max_form_elements,
+                                    elements.len().saturating_sub(max_form_elements)
+                                );
+                                element_start = visible_start(selected, element_start, max_form_elements);
+                            }
                         }
                     },
                     Key::PageUp => {
@@ -1211,6 +2720,31 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     }
                                 }
                             }
+                        } else if selected != !0 && ! elements.is_empty() {
+                            // Move a page up, to the first selectable element on it
+                            let target = selected.saturating_sub(cmp::max(max_form_elements, 1));
+                            let previous_opt = selectable_from(&elements, target, true)
+                                .filter(|&i| i < selected)
+                                .or_else(|| selectable_from(&elements, target, false));
+                            if let Some(previous) = previous_opt {
+                                selected = previous;
+                                element_start = element_start.saturating_sub(max_form_elements);
+                                element_start = visible_start(selected, element_start, max_form_elements);
+                            }
+                        }
+                    },
+                    Key::Home => if ! editing && ! elements.is_empty() {
+                        if let Some(first) = selectable_from(&elements, 0, true) {
+                            selected = first;
+                            element_start = 0;
+                            element_start = visible_start(selected, element_start, max_form_elements);
+                        }
+                    },
+                    Key::End => if ! editing && ! elements.is_empty() {
+                        if let Some(last) = selectable_from(&elements, elements.len() - 1, false) {
+                            selected = last;
+                            element_start = elements.len().saturating_sub(max_form_elements);
+                            element_start = visible_start(selected, element_start, max_form_elements);
                         }
                     },
                     _ => (),
@@ -1231,13 +2765,87 @@ extern "win64" fn form_display(form: &Form, user_input: &mut UserInput) -> Statu
     }
 }
 
+/// Show a message on top of the current form, until it is dismissed
+fn alert(displays: &mut [Display], font: &Font, title: &str, message: &str) -> Result<()> {
+    let theme = theme();
+    Popup::new(font, &theme, title, message, &["OK"]).show(displays, display_scale)?;
+    Ok(())
+}
+
 extern "win64" fn exit_display() {
     debugln!("exit_display");
+
+    unsafe {
+        if ! DISPLAYS.is_null() {
+            // Do not leave the last form on screen for whatever boots next
+            let mut displays = Box::from_raw(DISPLAYS);
+            for display in displays.iter_mut() {
+                display.set(Color::rgb(0, 0, 0));
+                display.sync();
+            }
+            DISPLAYS = ptr::null_mut();
+        }
+
+        // Cached texts refer to the font, so they must be dropped first
+        if ! TEXTS.is_null() {
+            drop(Box::from_raw(TEXTS));
+            TEXTS = ptr::null_mut();
+        }
+
+        if ! FONT.is_null() {
+            drop(Box::from_raw(FONT as *mut Font));
+            FONT = ptr::null();
+        }
+
+        if ! CHECKBOX_CHECKED.is_null() {
+            drop(Box::from_raw(CHECKBOX_CHECKED as *mut Image));
+            CHECKBOX_CHECKED = ptr::null();
+        }
+
+        if ! CHECKBOX_UNCHECKED.is_null() {
+            drop(Box::from_raw(CHECKBOX_UNCHECKED as *mut Image));
+            CHECKBOX_UNCHECKED = ptr::null();
+        }
+
+        if ! POINTER.is_null() {
+            drop(Box::from_raw(POINTER));
+            POINTER = ptr::null_mut();
+        }
+
+        if ! HII_IMAGES.is_null() {
+            drop(Box::from_raw(HII_IMAGES));
+            HII_IMAGES = ptr::null_mut();
+        }
+    }
+}
+
+fn confirm_data_change_inner() -> Result<usize> {
+    let displays = displays()?;
+    let font = font()?;
+    let theme = theme();
+    let popup = Popup::new(
+        font,
+        &theme,
+        "Save Changes?",
+        "Changes have been made that are not saved.",
+        &["Save", "Discard", "Cancel"]
+    );
+    match popup.show(displays, display_scale)? {
+        Some(0) => Ok(BROWSER_SAVE_CHANGES),
+        Some(1) => Ok(BROWSER_DISCARD_CHANGES),
+        _ => Ok(BROWSER_KEEP_CURRENT),
+    }
 }
 
 extern "win64" fn confirm_data_change() -> usize {
     debugln!("confirm_data_change");
-    0
+    match confirm_data_change_inner() {
+        Ok(ok) => ok,
+        Err(err) => {
+            debugln!("failed to confirm data change: {:?}", err);
+            BROWSER_KEEP_CURRENT
+        }
+    }
 }
 
 impl Fde {
@@ -0,0 +1,66 @@
+use core::ptr;
+use std::ops::Try;
+use std::proto::Protocol;
+use uefi::fs::{File, SimpleFileSystem, FILE_MODE_READ};
+use uefi::Handle;
+use uefi::guid::{Guid, SIMPLE_FILE_SYSTEM_GUID};
+use uefi::status::{Error, Result};
+
+struct FileSystem(&'static mut SimpleFileSystem);
+
+impl Protocol<SimpleFileSystem> for FileSystem {
+    fn guid() -> Guid {
+        SIMPLE_FILE_SYSTEM_GUID
+    }
+
+    fn new(inner: &'static mut SimpleFileSystem) -> Self {
+        FileSystem(inner)
+    }
+}
+
+/// Read a whole file from the first volume that has it, normally the ESP. The path uses
+/// backslashes and is relative to the root of the volume. Only volumes that are already
+/// connected are searched, so this should not be used at driver entry
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    for handle in FileSystem::locate_handle()? {
+        match read_handle(handle, path) {
+            Ok(ok) => return Ok(ok),
+            Err(Error::NotFound) => (),
+            Err(err) => debugln!("fs: failed to read {} from {:?}: {:?}", path, handle, err),
+        }
+    }
+    Err(Error::NotFound)
+}
+
+/// Read a whole file from the volume on a handle
+fn read_handle(handle: Handle, path: &str) -> Result<Vec<u8>> {
+    let fs = FileSystem::handle_protocol(handle)?.0;
+
+    let mut root_ptr = ptr::null_mut::<File>();
+    (fs.OpenVolume)(fs, &mut root_ptr)?;
+    let root = unsafe { root_ptr.as_mut() }.ok_or(Error::NotFound)?;
+
+    let path_w: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    let mut file_ptr = ptr::null_mut::<File>();
+    let status = (root.Open)(root, &mut file_ptr, path_w.as_ptr(), FILE_MODE_READ, 0);
+    let _ = (root.Close)(root);
+    status.into_result()?;
+    let file = unsafe { file_ptr.as_mut() }.ok_or(Error::NotFound)?;
+
+    let mut data = Vec::new();
+    let result = loop {
+        let mut buf = [0; 4096];
+        let mut count = buf.len();
+        match (file.Read)(file, &mut count, buf.as_mut_ptr()).into_result() {
+            Ok(_) => if count == 0 {
+                break Ok(data);
+            } else {
+                data.extend_from_slice(&buf[..count]);
+            },
+            Err(err) => break Err(err),
+        }
+    };
+    let _ = (file.Close)(file);
+
+    result
+}
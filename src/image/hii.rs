@@ -0,0 +1,180 @@
+use super::Image;
+
+// HII image block types
+const IIBT_END: u8 = 0x00;
+const IIBT_IMAGE_1BIT: u8 = 0x10;
+const IIBT_IMAGE_1BIT_TRANS: u8 = 0x11;
+const IIBT_IMAGE_24BIT: u8 = 0x12;
+const IIBT_IMAGE_24BIT_TRANS: u8 = 0x13;
+const IIBT_IMAGE_4BIT: u8 = 0x14;
+const IIBT_IMAGE_4BIT_TRANS: u8 = 0x15;
+const IIBT_IMAGE_8BIT: u8 = 0x16;
+const IIBT_IMAGE_8BIT_TRANS: u8 = 0x17;
+const IIBT_IMAGE_JPEG: u8 = 0x18;
+const IIBT_IMAGE_PNG: u8 = 0x19;
+const IIBT_DUPLICATE: u8 = 0x20;
+const IIBT_SKIP2: u8 = 0x21;
+const IIBT_SKIP1: u8 = 0x22;
+const IIBT_EXT1: u8 = 0x30;
+const IIBT_EXT2: u8 = 0x31;
+const IIBT_EXT4: u8 = 0x32;
+
+/// Parse an image from an HII image package, starting at its package header
+pub fn parse(package_data: &[u8], image_id: u16) -> Result<Image, String> {
+    use orbclient::Color;
+
+    let get = |i: usize| -> u8 {
+        match package_data.get(i) {
+            Some(byte) => *byte,
+            None => 0,
+        }
+    };
+
+    let getw = |i: usize| -> u16 { (get(i) as u16) + ((get(i + 1) as u16) << 8) };
+
+    let getd = |i: usize| -> u32 {
+        (get(i) as u32) + ((get(i + 1) as u32) << 8) + ((get(i + 2) as u32) << 16) +
+        ((get(i + 3) as u32) << 24)
+    };
+
+    // Colors are stored as blue, green, red
+    let getc = |i: usize| -> Color { Color::rgb(get(i + 2), get(i + 1), get(i)) };
+
+    let palette = |palette_index: u8| -> Result<Vec<Color>, String> {
+        let palette_offset = getd(8) as usize;
+        if palette_offset == 0 {
+            return Err("image package has no palettes".to_string());
+        }
+
+        // Palettes are numbered from 1
+        let count = getw(palette_offset);
+        let mut i = palette_offset + 2;
+        for index in 1..=count {
+            let size = getw(i) as usize;
+            if index == palette_index as u16 {
+                return Ok((0..size / 3).map(|j| getc(i + 2 + j * 3)).collect());
+            }
+            i += 2 + size;
+        }
+
+        Err(format!("palette {} not found", palette_index))
+    };
+
+    let mut id = 1u32;
+    let mut i = getd(4) as usize;
+    while i < package_data.len() {
+        let kind = get(i);
+        match kind {
+            IIBT_END => break,
+            IIBT_IMAGE_1BIT | IIBT_IMAGE_1BIT_TRANS |
+            IIBT_IMAGE_4BIT | IIBT_IMAGE_4BIT_TRANS |
+            IIBT_IMAGE_8BIT | IIBT_IMAGE_8BIT_TRANS => {
+                let width = getw(i + 2) as usize;
+                let height = getw(i + 4) as usize;
+                let bits = match kind {
+                    IIBT_IMAGE_1BIT | IIBT_IMAGE_1BIT_TRANS => 1,
+                    IIBT_IMAGE_4BIT | IIBT_IMAGE_4BIT_TRANS => 4,
+                    _ => 8,
+                };
+                // Rows are padded to a whole byte
+                let row_bytes = (width * bits + 7) / 8;
+
+                if id == image_id as u32 {
+                    let colors = palette(get(i + 1))?;
+                    let transparent = kind == IIBT_IMAGE_1BIT_TRANS
+                        || kind == IIBT_IMAGE_4BIT_TRANS
+                        || kind == IIBT_IMAGE_8BIT_TRANS;
+
+                    let mut data = Vec::with_capacity(width * height);
+                    for y in 0..height {
+                        for x in 0..width {
+                            let byte = get(i + 6 + y * row_bytes + x * bits / 8);
+                            let shift = 8 - bits - (x * bits) % 8;
+                            let index = (byte >> shift) & (0xFF >> (8 - bits));
+                            if transparent && index == 0 {
+                                data.push(Color::rgba(0, 0, 0, 0));
+                            } else {
+                                data.push(colors.get(index as usize).cloned().unwrap_or(Color::rgb(0, 0, 0)));
+                            }
+                        }
+                    }
+
+                    return Image::from_data(width as u32, height as u32, data.into_boxed_slice());
+                }
+
+                i += 6 + row_bytes * height;
+                id += 1;
+            },
+            IIBT_IMAGE_24BIT | IIBT_IMAGE_24BIT_TRANS => {
+                let width = getw(i + 1) as usize;
+                let height = getw(i + 3) as usize;
+
+                if id == image_id as u32 {
+                    let transparent = kind == IIBT_IMAGE_24BIT_TRANS;
+
+                    let mut data = Vec::with_capacity(width * height);
+                    for pixel in 0..width * height {
+                        let color = getc(i + 5 + pixel * 3);
+                        // Transparent true color images use black for the background
+                        if transparent && color.data & 0xFFFFFF == 0 {
+                            data.push(Color::rgba(0, 0, 0, 0));
+                        } else {
+                            data.push(color);
+                        }
+                    }
+
+                    return Image::from_data(width as u32, height as u32, data.into_boxed_slice());
+                }
+
+                i += 5 + width * height * 3;
+                id += 1;
+            },
+            IIBT_IMAGE_JPEG | IIBT_IMAGE_PNG => {
+                if id == image_id as u32 {
+                    return Err(format!("image {} uses unsupported block {:#x}", image_id, kind));
+                }
+
+                i += 5 + getd(i + 1) as usize;
+                id += 1;
+            },
+            IIBT_DUPLICATE => {
+                if id == image_id as u32 {
+                    let duplicate_id = getw(i + 1);
+                    if duplicate_id >= image_id {
+                        return Err(format!("image {} duplicates invalid image {}", image_id, duplicate_id));
+                    }
+                    return parse(package_data, duplicate_id);
+                }
+
+                i += 3;
+                id += 1;
+            },
+            IIBT_SKIP1 => {
+                id += get(i + 1) as u32;
+                i += 2;
+            },
+            IIBT_SKIP2 => {
+                id += getw(i + 1) as u32;
+                i += 3;
+            },
+            IIBT_EXT1 | IIBT_EXT2 | IIBT_EXT4 => {
+                let length = match kind {
+                    IIBT_EXT1 => get(i + 2) as usize,
+                    IIBT_EXT2 => getw(i + 2) as usize,
+                    _ => getd(i + 2) as usize,
+                };
+                if length == 0 {
+                    return Err(format!("invalid extended image block {:#x}", get(i + 1)));
+                }
+                i += length;
+            },
+            unknown => return Err(format!("unknown image block {:#x}", unknown)),
+        }
+
+        if id > image_id as u32 {
+            break;
+        }
+    }
+
+    Err(format!("image {} not found", image_id))
+}
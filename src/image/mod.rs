@@ -4,6 +4,7 @@ use core::cmp;
 use orbclient::{Color, Mode, Renderer};
 
 pub mod bmp;
+pub mod hii;
 
 pub struct ImageRoi<'a> {
     x: u32,
@@ -82,6 +83,24 @@ impl Image {
         }
     }
 
+    /// Create a copy of the image with a new size, using the nearest pixels
+    pub fn resize(&self, width: u32, height: u32) -> Self {
+        // There are no pixels to sample from an empty image
+        if self.w == 0 || self.h == 0 {
+            return self.clone();
+        }
+
+        let mut data = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            let src_y = (y as u64 * self.h as u64 / height as u64) as u32;
+            for x in 0..width {
+                let src_x = (x as u64 * self.w as u64 / width as u64) as u32;
+                data.push(self.data[(src_y * self.w + src_x) as usize]);
+            }
+        }
+        Self::from_data(width, height, data.into_boxed_slice()).unwrap()
+    }
+
     /// Return a boxed slice of colors making up the image
     pub fn into_data(self) -> Box<[Color]> {
         self.data
@@ -1,7 +1,52 @@
 use core::char;
-use uefi::status::Result;
+use std::proto::Protocol;
+use uefi::Event;
+use uefi::guid::Guid;
+use uefi::status::{Result, Status};
 use uefi::text::TextInputKey;
 
+// TODO: move to uefi library {
+pub const SIMPLE_TEXT_INPUT_EX_GUID: Guid = Guid(0xdd9e7534, 0x7762, 0x4698, [0x8c, 0x14, 0xf5, 0x85, 0x17, 0xa6, 0x25, 0xaa]);
+
+pub const SHIFT_STATE_VALID: u32 = 0x80000000;
+pub const RIGHT_CONTROL_PRESSED: u32 = 0x00000004;
+pub const LEFT_CONTROL_PRESSED: u32 = 0x00000008;
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct KeyState {
+    pub KeyShiftState: u32,
+    pub KeyToggleState: u8,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct KeyData {
+    pub Key: TextInputKey,
+    pub KeyState: KeyState,
+}
+
+#[repr(C)]
+pub struct TextInputEx {
+    pub Reset: extern "win64" fn(&TextInputEx, ExtendedVerification: bool) -> Status,
+    pub ReadKeyStrokeEx: extern "win64" fn(&TextInputEx, KeyData: &mut KeyData) -> Status,
+    pub WaitForKeyEx: Event,
+    pub SetState: extern "win64" fn(&TextInputEx, KeyToggleState: &u8) -> Status,
+    pub RegisterKeyNotify: extern "win64" fn(&TextInputEx, KeyData: &KeyData, KeyNotificationFunction: usize, NotifyHandle: &mut usize) -> Status,
+    pub UnregisterKeyNotify: extern "win64" fn(&TextInputEx, NotificationHandle: usize) -> Status,
+}
+
+impl Protocol<TextInputEx> for &'static mut TextInputEx {
+    fn guid() -> Guid {
+        SIMPLE_TEXT_INPUT_EX_GUID
+    }
+
+    fn new(inner: &'static mut TextInputEx) -> Self {
+        inner
+    }
+}
+// } TODO: move to uefi library
+
 #[derive(Debug)]
 pub enum Key {
     Backspace,
@@ -89,6 +134,41 @@ pub fn raw_key(wait: bool) -> Result<TextInputKey> {
     Ok(key)
 }
 
+/// Read a key along with whether control was held. Consoles without the extended text input
+/// protocol never report control
+pub fn raw_key_control(wait: bool) -> Result<(TextInputKey, bool)> {
+    let uefi = std::system_table();
+
+    let input_ex = match <&'static mut TextInputEx>::handle_protocol(uefi.ConsoleInHandle) {
+        Ok(ok) => ok,
+        Err(_) => return Ok((raw_key(wait)?, false)),
+    };
+
+    if wait {
+        let mut index = 0;
+        (uefi.BootServices.WaitForEvent)(1, &input_ex.WaitForKeyEx, &mut index)?;
+    }
+
+    let mut key_data = KeyData {
+        Key: TextInputKey {
+            ScanCode: 0,
+            UnicodeChar: 0
+        },
+        KeyState: KeyState {
+            KeyShiftState: 0,
+            KeyToggleState: 0,
+        },
+    };
+
+    (input_ex.ReadKeyStrokeEx)(input_ex, &mut key_data)?;
+
+    let shift_state = key_data.KeyState.KeyShiftState;
+    let control = shift_state & SHIFT_STATE_VALID != 0
+        && shift_state & (LEFT_CONTROL_PRESSED | RIGHT_CONTROL_PRESSED) != 0;
+
+    Ok((key_data.Key, control))
+}
+
 pub fn key(wait: bool) -> Result<Key> {
     let raw_key = raw_key(wait)?;
     Ok(Key::from(raw_key))
@@ -32,14 +32,23 @@ use uefi::status::Status;
 #[macro_use]
 mod debug;
 
+mod accessibility;
 mod coreboot;
 mod display;
+mod edid;
+mod editor;
+mod fs;
 mod hii;
 pub mod image;
 mod key;
 pub mod null;
+mod pointer;
+mod popup;
+mod resolution;
 mod serial;
 pub mod text;
+mod theme;
+mod variable;
 
 //mod dump_hii;
 mod fde;
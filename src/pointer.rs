@@ -0,0 +1,294 @@
+use core::cmp;
+use orbclient::{Color, Renderer};
+use std::ops::Try;
+use std::proto::Protocol;
+use uefi::Event;
+use uefi::guid::Guid;
+use uefi::status::{Error, Status};
+
+use crate::display::{scaled, Rotation};
+use crate::image::Image;
+
+// TODO: move to uefi library {
+pub const SIMPLE_POINTER_GUID: Guid = Guid(0x31878c87, 0x0b75, 0x11d5, [0x9a, 0x4f, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+
+#[repr(C)]
+pub struct SimplePointerMode {
+    pub ResolutionX: u64,
+    pub ResolutionY: u64,
+    pub ResolutionZ: u64,
+    pub LeftButton: bool,
+    pub RightButton: bool,
+}
+
+#[repr(C)]
+pub struct SimplePointerState {
+    pub RelativeMovementX: i32,
+    pub RelativeMovementY: i32,
+    pub RelativeMovementZ: i32,
+    pub LeftButton: bool,
+    pub RightButton: bool,
+}
+
+#[repr(C)]
+pub struct SimplePointer {
+    pub Reset: extern "win64" fn(&SimplePointer, ExtendedVerification: bool) -> Status,
+    pub GetState: extern "win64" fn(&SimplePointer, State: &mut SimplePointerState) -> Status,
+    pub WaitForInput: Event,
+    pub Mode: &'static SimplePointerMode,
+}
+
+impl Protocol<SimplePointer> for &'static mut SimplePointer {
+    fn guid() -> Guid {
+        SIMPLE_POINTER_GUID
+    }
+
+    fn new(inner: &'static mut SimplePointer) -> Self {
+        inner
+    }
+}
+
+pub const ABSOLUTE_POINTER_GUID: Guid = Guid(0x8d59d32b, 0xc655, 0x4ae9, [0x9b, 0x15, 0xf2, 0x59, 0x04, 0x99, 0x2a, 0x43]);
+
+pub const ABSP_TOUCH_ACTIVE: u32 = 0x00000001;
+
+#[repr(C)]
+pub struct AbsolutePointerMode {
+    pub AbsoluteMinX: u64,
+    pub AbsoluteMinY: u64,
+    pub AbsoluteMinZ: u64,
+    pub AbsoluteMaxX: u64,
+    pub AbsoluteMaxY: u64,
+    pub AbsoluteMaxZ: u64,
+    pub Attributes: u32,
+}
+
+#[repr(C)]
+pub struct AbsolutePointerState {
+    pub CurrentX: u64,
+    pub CurrentY: u64,
+    pub CurrentZ: u64,
+    pub ActiveButtons: u32,
+}
+
+#[repr(C)]
+pub struct AbsolutePointer {
+    pub Reset: extern "win64" fn(&AbsolutePointer, ExtendedVerification: bool) -> Status,
+    pub GetState: extern "win64" fn(&AbsolutePointer, State: &mut AbsolutePointerState) -> Status,
+    pub WaitForInput: Event,
+    pub Mode: &'static AbsolutePointerMode,
+}
+
+impl Protocol<AbsolutePointer> for &'static mut AbsolutePointer {
+    fn guid() -> Guid {
+        ABSOLUTE_POINTER_GUID
+    }
+
+    fn new(inner: &'static mut AbsolutePointer) -> Self {
+        inner
+    }
+}
+// } TODO: move to uefi library
+
+// Arrow cursor, X is the outline, . is the fill, and space is transparent
+static CURSOR: [&'static str; 17] = [
+    "X          ",
+    "XX         ",
+    "X.X        ",
+    "X..X       ",
+    "X...X      ",
+    "X....X     ",
+    "X.....X    ",
+    "X......X   ",
+    "X.......X  ",
+    "X........X ",
+    "X.....XXXXX",
+    "X..X..X    ",
+    "X.X X..X   ",
+    "XX  X..X   ",
+    "X    X..X  ",
+    "     X..X  ",
+    "      XX   ",
+];
+
+/// Pixels the cursor moves at a scale of 1 for each millimeter a pointer is moved, for pointers
+/// that report their resolution
+const PIXELS_PER_MM: f32 = 16.0;
+
+/// Movement in pixels for a relative movement in counts, given the counts per millimeter
+fn movement(counts: i32, resolution: u64, scale: f32) -> f32 {
+    if resolution == 0 {
+        counts as f32 * scale
+    } else {
+        counts as f32 * PIXELS_PER_MM * scale / resolution as f32
+    }
+}
+
+pub enum PointerEvent {
+    /// The cursor moved
+    Move,
+    /// The primary button was pressed at a position
+    Click(i32, i32),
+    /// The wheel was turned by a number of rows, positive is down
+    Scroll(i32),
+}
+
+pub struct Pointer {
+    simple: Vec<(&'static mut SimplePointer, bool)>,
+    absolute: Vec<(&'static mut AbsolutePointer, bool)>,
+    x: i32,
+    y: i32,
+    // Movement of less than a pixel, kept so slow movements are not lost
+    remainder_x: f32,
+    remainder_y: f32,
+    visible: bool,
+    cursor: Image,
+}
+
+impl Pointer {
+    pub fn new() -> Self {
+        let mut cursor = Image::new(CURSOR[0].len() as u32, CURSOR.len() as u32);
+        for (y, row) in CURSOR.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let color = match c {
+                    'X' => Color::rgb(0x00, 0x00, 0x00),
+                    '.' => Color::rgb(0xFF, 0xFF, 0xFF),
+                    _ => Color::rgba(0x00, 0x00, 0x00, 0x00),
+                };
+                cursor.data_mut()[y * CURSOR[0].len() + x] = color;
+            }
+        }
+
+        Self {
+            simple: <&'static mut SimplePointer>::all().into_iter().map(|simple| (simple, false)).collect(),
+            absolute: <&'static mut AbsolutePointer>::all().into_iter().map(|absolute| (absolute, false)).collect(),
+            x: 0,
+            y: 0,
+            remainder_x: 0.0,
+            remainder_y: 0.0,
+            visible: false,
+            cursor,
+        }
+    }
+
+    /// Events that are signaled when any pointer has input
+    pub fn events(&self) -> Vec<Event> {
+        let mut events = Vec::new();
+        for (simple, _) in self.simple.iter() {
+            events.push(simple.WaitForInput);
+        }
+        for (absolute, _) in self.absolute.iter() {
+            events.push(absolute.WaitForInput);
+        }
+        events
+    }
+
+    /// Read the state of every pointer, moving the cursor within a display of size w by h.
+    /// Absolute pointers, such as touchscreens, are mapped through the rotation of the display
+    pub fn poll(&mut self, w: u32, h: u32, scale: f32, rotation: Rotation) -> Vec<PointerEvent> {
+        let mut events = Vec::new();
+
+        for (simple, left) in self.simple.iter_mut() {
+            let mut state = SimplePointerState {
+                RelativeMovementX: 0,
+                RelativeMovementY: 0,
+                RelativeMovementZ: 0,
+                LeftButton: false,
+                RightButton: false,
+            };
+            match (simple.GetState)(simple, &mut state).into_result() {
+                Ok(_) => (),
+                Err(Error::NotReady) => continue,
+                Err(err) => {
+                    debugln!("failed to get simple pointer state: {:?}", err);
+                    continue;
+                }
+            }
+
+            if state.RelativeMovementX != 0 || state.RelativeMovementY != 0 {
+                let mode = simple.Mode;
+                let dx = self.remainder_x + movement(state.RelativeMovementX, mode.ResolutionX, scale);
+                let dy = self.remainder_y + movement(state.RelativeMovementY, mode.ResolutionY, scale);
+                self.x += dx as i32;
+                self.y += dy as i32;
+                self.remainder_x = dx - (dx as i32) as f32;
+                self.remainder_y = dy - (dy as i32) as f32;
+                events.push(PointerEvent::Move);
+            }
+
+            if state.RelativeMovementZ != 0 {
+                // Turning the wheel away from the user scrolls up
+                events.push(PointerEvent::Scroll(-state.RelativeMovementZ.signum()));
+            }
+
+            if state.LeftButton && ! *left {
+                events.push(PointerEvent::Click(self.x, self.y));
+            }
+            *left = state.LeftButton;
+
+            self.visible = true;
+        }
+
+        for (absolute, touch) in self.absolute.iter_mut() {
+            let mut state = AbsolutePointerState {
+                CurrentX: 0,
+                CurrentY: 0,
+                CurrentZ: 0,
+                ActiveButtons: 0,
+            };
+            match (absolute.GetState)(absolute, &mut state).into_result() {
+                Ok(_) => (),
+                Err(Error::NotReady) => continue,
+                Err(err) => {
+                    debugln!("failed to get absolute pointer state: {:?}", err);
+                    continue;
+                }
+            }
+
+            let mode = absolute.Mode;
+            if mode.AbsoluteMaxX > mode.AbsoluteMinX && mode.AbsoluteMaxY > mode.AbsoluteMinY {
+                let current_x = cmp::min(cmp::max(state.CurrentX, mode.AbsoluteMinX), mode.AbsoluteMaxX);
+                let current_y = cmp::min(cmp::max(state.CurrentY, mode.AbsoluteMinY), mode.AbsoluteMaxY);
+                let (x, y, max_x, max_y) = rotation.to_display(
+                    current_x - mode.AbsoluteMinX,
+                    current_y - mode.AbsoluteMinY,
+                    mode.AbsoluteMaxX - mode.AbsoluteMinX,
+                    mode.AbsoluteMaxY - mode.AbsoluteMinY
+                );
+                self.x = (x * w as u64 / max_x) as i32;
+                self.y = (y * h as u64 / max_y) as i32;
+                events.push(PointerEvent::Move);
+            }
+
+            let active = state.ActiveButtons & ABSP_TOUCH_ACTIVE != 0;
+            if active && ! *touch {
+                events.push(PointerEvent::Click(self.x, self.y));
+            }
+            *touch = active;
+
+            self.visible = true;
+        }
+
+        self.x = cmp::max(0, cmp::min(self.x, w as i32 - 1));
+        self.y = cmp::max(0, cmp::min(self.y, h as i32 - 1));
+
+        events
+    }
+
+    /// Draw the cursor at its current position, it is only shown once a pointer has been used
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, scale: f32) {
+        if ! self.visible {
+            return;
+        }
+
+        if scale != 1.0 {
+            let cursor = self.cursor.resize(
+                scaled(self.cursor.width() as i32, scale) as u32,
+                scaled(self.cursor.height() as i32, scale) as u32
+            );
+            cursor.draw(renderer, self.x, self.y);
+        } else {
+            self.cursor.draw(renderer, self.x, self.y);
+        }
+    }
+}
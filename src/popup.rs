@@ -0,0 +1,252 @@
+use core::cmp;
+use orbclient::{Color, Renderer};
+use orbfont::{Font, Text};
+use uefi::status::Result;
+
+use crate::display::{scaled, Display};
+use crate::key::{raw_key, Key};
+use crate::theme::Theme;
+
+/// Modal box drawn on top of every display, with a title, message and a row of buttons
+pub struct Popup<'a> {
+    font: &'a Font,
+    theme: &'a Theme,
+    title: &'a str,
+    message: &'a str,
+    buttons: &'a [&'a str],
+    selected: usize,
+}
+
+/// The popup rendered at the scale of one display, and the region of the display it covers
+struct PopupLayout<'a> {
+    padding_lr: i32,
+    padding_tb: i32,
+    margin_lr: i32,
+    margin_tb: i32,
+    title: Text<'a>,
+    lines: Vec<Text<'a>>,
+    buttons: Vec<Text<'a>>,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    saved: Vec<Color>,
+}
+
+impl<'a> PopupLayout<'a> {
+    fn new(popup: &Popup<'a>, display: &Display, scale: f32) -> Self {
+        let font = popup.font;
+        let theme = popup.theme;
+        let font_size = scaled(theme.font_size, scale) as f32;
+        let margin_lr = scaled(theme.margin_lr, scale);
+        // Long messages, such as errors from drivers, are wrapped to fit inside the box
+        let max_line_w = display.width() as i32 - margin_lr * 4;
+        let mut layout = Self {
+            padding_lr: scaled(theme.padding_lr, scale),
+            padding_tb: scaled(theme.padding_tb, scale),
+            margin_lr,
+            margin_tb: scaled(theme.margin_tb, scale),
+            title: font.render(popup.title, scaled(theme.title_font_size, scale) as f32),
+            lines: popup.message.lines().flat_map(|line| {
+                wrap(font, line, font_size, max_line_w)
+            }).collect(),
+            buttons: popup.buttons.iter().map(|button| {
+                font.render(button, font_size)
+            }).collect(),
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+            saved: Vec::new(),
+        };
+
+        let margin_tb = layout.margin_tb;
+        let padding_tb = layout.padding_tb;
+
+        let (buttons_w, buttons_h) = layout.buttons_size();
+        let mut inner_w = cmp::max(layout.title.width() as i32, buttons_w);
+        let mut inner_h = layout.title.height() as i32 + margin_tb * 2;
+        for line in layout.lines.iter() {
+            inner_w = cmp::max(inner_w, line.width() as i32);
+            inner_h += line.height() as i32;
+        }
+        inner_h += margin_tb * 4 + buttons_h + padding_tb * 2;
+
+        let (display_w, display_h) = (display.width() as i32, display.height() as i32);
+        layout.w = cmp::min(inner_w + margin_lr * 4, display_w);
+        layout.h = cmp::min(inner_h + margin_tb * 4, display_h);
+        layout.x = (display_w - layout.w) / 2;
+        layout.y = (display_h - layout.h) / 2;
+        layout.saved = save_under(display, layout.x, layout.y, layout.w, layout.h);
+
+        layout
+    }
+
+    fn buttons_size(&self) -> (i32, i32) {
+        let padding_lr = self.padding_lr;
+        let margin_lr = self.margin_lr;
+        let mut w = 0;
+        let mut h = 0;
+        for (i, rendered) in self.buttons.iter().enumerate() {
+            if i > 0 {
+                w += margin_lr;
+            }
+            w += rendered.width() as i32 + padding_lr * 2;
+            h = cmp::max(h, rendered.height() as i32);
+        }
+        (w, h)
+    }
+
+    fn draw(&self, display: &mut Display, theme: &Theme, selected: usize) {
+        let (x, y, w, h) = (self.x, self.y, self.w, self.h);
+        let padding_lr = self.padding_lr;
+        let padding_tb = self.padding_tb;
+        let margin_lr = self.margin_lr;
+        let margin_tb = self.margin_tb;
+        let border = 2;
+
+        display.rect(x, y, w as u32, h as u32, theme.outline_color);
+        display.rect(x + border, y + border, (w - border * 2) as u32, (h - border * 2) as u32, theme.background_color);
+
+        let mut text_y = y + margin_tb * 2;
+        self.title.draw(display, x + (w - self.title.width() as i32) / 2, text_y, theme.text_color);
+        text_y += self.title.height() as i32 + margin_tb * 2;
+
+        for line in self.lines.iter() {
+            line.draw(display, x + (w - line.width() as i32) / 2, text_y, theme.text_color);
+            text_y += line.height() as i32;
+        }
+        text_y += margin_tb * 4;
+
+        let (buttons_w, buttons_h) = self.buttons_size();
+        let mut button_x = x + (w - buttons_w) / 2;
+        for (i, rendered) in self.buttons.iter().enumerate() {
+            let button_w = rendered.width() as i32 + padding_lr * 2;
+            let button_h = buttons_h + padding_tb * 2;
+            let text_color = if i == selected {
+                display.rect(button_x, text_y, button_w as u32, button_h as u32, theme.highlight_color);
+                theme.highlight_text_color
+            } else {
+                display.rect(button_x, text_y, button_w as u32, button_h as u32, theme.outline_color);
+                display.rect(button_x + border, text_y + border, (button_w - border * 2) as u32, (button_h - border * 2) as u32, theme.background_color);
+                theme.text_color
+            };
+            rendered.draw(display, button_x + padding_lr, text_y + padding_tb, text_color);
+            button_x += button_w + margin_lr;
+        }
+    }
+}
+
+impl<'a> Popup<'a> {
+    pub fn new(font: &'a Font, theme: &'a Theme, title: &'a str, message: &'a str, buttons: &'a [&'a str]) -> Self {
+        Self {
+            font,
+            theme,
+            title,
+            message,
+            buttons,
+            selected: 0,
+        }
+    }
+
+    /// Show the popup on every display, each at its own scale, and wait for a button to be
+    /// chosen, restoring the covered regions afterwards.
+    /// Returns the index of the chosen button, or None if the popup was dismissed with escape
+    pub fn show<F: Fn(&Display) -> f32>(mut self, displays: &mut [Display], scale: F) -> Result<Option<usize>> {
+        let layouts: Vec<PopupLayout> = displays.iter().map(|display| {
+            PopupLayout::new(&self, display, scale(display))
+        }).collect();
+
+        let result = loop {
+            for (display, layout) in displays.iter_mut().zip(layouts.iter()) {
+                layout.draw(display, self.theme, self.selected);
+                display.sync_damage();
+            }
+
+            let key = match raw_key(true) {
+                Ok(ok) => Key::from(ok),
+                Err(err) => break Err(err),
+            };
+
+            match key {
+                Key::Left | Key::Up => if self.selected > 0 {
+                    self.selected -= 1;
+                } else {
+                    self.selected = cmp::max(self.buttons.len(), 1) - 1;
+                },
+                Key::Right | Key::Down | Key::Tab => if self.selected + 1 < self.buttons.len() {
+                    self.selected += 1;
+                } else {
+                    self.selected = 0;
+                },
+                Key::Enter => break Ok(Some(self.selected)),
+                Key::Escape => break Ok(None),
+                _ => (),
+            }
+        };
+
+        for (display, layout) in displays.iter_mut().zip(layouts.iter()) {
+            restore_under(display, layout.x, layout.y, layout.w, layout.h, &layout.saved);
+            display.sync_damage();
+        }
+
+        result
+    }
+}
+
+/// Render a line of text, breaking it into lines no wider than max_w. Lines are broken between
+/// words, and words that do not fit on a line of their own are broken between characters
+fn wrap<'a>(font: &'a Font, line: &str, font_size: f32, max_w: i32) -> Vec<Text<'a>> {
+    let fits = |text: &str| font.render(text, font_size).width() as i32 <= max_w;
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split(' ') {
+        let joined = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if fits(&joined) {
+            current = joined;
+            continue;
+        }
+
+        if ! current.is_empty() {
+            lines.push(current);
+        }
+        current = String::new();
+        for c in word.chars() {
+            current.push(c);
+            if current.chars().count() > 1 && ! fits(&current) {
+                current.pop();
+                lines.push(current);
+                current = c.to_string();
+            }
+        }
+    }
+    lines.push(current);
+
+    lines.iter().map(|line| font.render(line, font_size)).collect()
+}
+
+/// Copy a region of the display, so it can be put back when a popup is closed
+fn save_under(display: &Display, x: i32, y: i32, w: i32, h: i32) -> Vec<Color> {
+    let stride = display.width() as usize;
+    let data = display.data();
+    let mut saved = Vec::with_capacity(w as usize * h as usize);
+    for row in y..y + h {
+        let offset = row as usize * stride + x as usize;
+        saved.extend_from_slice(&data[offset..offset + w as usize]);
+    }
+    saved
+}
+
+fn restore_under(display: &mut Display, x: i32, y: i32, w: i32, h: i32, saved: &[Color]) {
+    let stride = display.width() as usize;
+    let data = display.data_mut();
+    for (i, row) in (y..y + h).enumerate() {
+        let offset = row as usize * stride + x as usize;
+        data[offset..offset + w as usize].copy_from_slice(&saved[i * w as usize..(i + 1) * w as usize]);
+    }
+}
@@ -0,0 +1,98 @@
+use orbclient::Renderer;
+
+use crate::display::Display;
+use crate::variable;
+
+const VARIABLE_NAME: &str = "Resolution";
+
+/// The resolution chosen by the user, if any
+pub fn configured() -> Option<(u32, u32)> {
+    let mut data = [0; 8];
+    match variable::get(VARIABLE_NAME, &mut data) {
+        Ok(8) => {
+            let w = data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16 | (data[3] as u32) << 24;
+            let h = data[4] as u32 | (data[5] as u32) << 8 | (data[6] as u32) << 16 | (data[7] as u32) << 24;
+            Some((w, h))
+        },
+        Ok(size) => {
+            debugln!("resolution: invalid variable size {}", size);
+            None
+        },
+        Err(_) => None,
+    }
+}
+
+/// Save the resolution chosen by the user, or go back to choosing it automatically
+fn configure(resolution_opt: Option<(u32, u32)>) {
+    let result = match resolution_opt {
+        Some((w, h)) => variable::set(VARIABLE_NAME, &[
+            w as u8, (w >> 8) as u8, (w >> 16) as u8, (w >> 24) as u8,
+            h as u8, (h >> 8) as u8, (h >> 16) as u8, (h >> 24) as u8,
+        ]),
+        // Writing no data deletes the variable
+        None => variable::set(VARIABLE_NAME, &[]),
+    };
+    if let Err(err) = result {
+        debugln!("resolution: failed to write variable: {:?}", err);
+    }
+}
+
+/// Choose the mode of a display. The configured resolution is used if it is supported, then the
+/// preferred resolution from the EDID, and otherwise the largest resolution, which is native on
+/// most panels. The configured resolution is chosen on the primary output, so it only applies
+/// there, leaving mirrored outputs at their own preferred resolution
+pub fn select(display: &mut Display, primary: bool) {
+    let modes = display.modes();
+
+    let find = |resolution: (u32, u32)| {
+        modes.iter().find(|mode| (mode.1, mode.2) == resolution).map(|mode| mode.0)
+    };
+
+    let configured_opt = if primary { configured() } else { None };
+    let mode_opt = configured_opt.and_then(find)
+        .or_else(|| display.edid().and_then(|edid| edid.preferred_resolution()).and_then(find))
+        .or_else(|| modes.iter().max_by_key(|mode| mode.1 as u64 * mode.2 as u64).map(|mode| mode.0));
+
+    if let Some(mode) = mode_opt {
+        set_mode(display, mode);
+    }
+}
+
+/// Switch the primary output to the next larger resolution, saving it as the configured one.
+/// After the largest, the resolution is chosen automatically again
+pub fn cycle(display: &mut Display) {
+    let mut modes = display.modes();
+    // The size of the display is rotated, so the current resolution comes from the mode
+    let current_opt = modes.iter().find(|mode| mode.0 == display.output_mode()).map(|mode| (mode.1, mode.2));
+    modes.sort_by_key(|mode| (mode.1 as u64 * mode.2 as u64, mode.1));
+    modes.dedup_by_key(|mode| (mode.1, mode.2));
+
+    let next_opt = match configured() {
+        Some(_) => modes.iter().position(|mode| Some((mode.1, mode.2)) == current_opt)
+            .and_then(|i| modes.get(i + 1)),
+        None => modes.first(),
+    };
+
+    match next_opt {
+        Some(&(mode, w, h)) => {
+            configure(Some((w, h)));
+            set_mode(display, mode);
+        },
+        None => {
+            configure(None);
+            select(display, true);
+        }
+    }
+}
+
+fn set_mode(display: &mut Display, mode: u32) {
+    if mode == display.output_mode() {
+        return;
+    }
+
+    if let Err(err) = display.set_output_mode(mode) {
+        debugln!("resolution: failed to set mode {}: {:?}", mode, err);
+    } else {
+        debugln!("resolution: set mode {}, {}x{}", mode, display.width(), display.height());
+    }
+}
@@ -1,11 +1,10 @@
 use core::{char, mem};
 use core::ops::Deref;
 use orbclient::{Color, Renderer};
-use std::proto::Protocol;
 use uefi::Handle;
 use uefi::boot::InterfaceType;
 use uefi::guid::SIMPLE_TEXT_OUTPUT_GUID;
-use uefi::status::{Result, Status};
+use uefi::status::{Error, Result, Status};
 use uefi::text::TextOutputMode;
 
 use crate::display::{Display, ScaledDisplay, Output};
@@ -29,7 +28,8 @@ pub struct TextDisplay<'a> {
     pub off_y: i32,
     pub cols: usize,
     pub rows: usize,
-    pub display: ScaledDisplay<'a>,
+    /// Every output shows the same text, using the columns and rows that fit on all of them
+    pub displays: Vec<ScaledDisplay<'a>>,
 }
 
 extern "win64" fn reset(_output: &mut TextDisplay, _extra: bool) -> Status {
@@ -76,7 +76,7 @@ extern "win64" fn enable_cursor(output: &mut TextDisplay, enable: bool) -> Statu
 }
 
 impl<'a> TextDisplay<'a> {
-    pub fn new(display: ScaledDisplay<'a>) -> TextDisplay<'a> {
+    pub fn new(displays: Vec<ScaledDisplay<'a>>) -> TextDisplay<'a> {
         let mode = Box::new(TextOutputMode {
             MaxMode: 0,
             Mode: 0,
@@ -86,8 +86,8 @@ impl<'a> TextDisplay<'a> {
             CursorVisible: false,
         });
 
-        let cols = display.width() as usize/8;
-        let rows = display.height() as usize/16;
+        let cols = displays.iter().map(|display| display.width() as usize/8).min().unwrap_or(0);
+        let rows = displays.iter().map(|display| display.height() as usize/16).min().unwrap_or(0);
 
         TextDisplay {
             Reset: reset,
@@ -106,7 +106,7 @@ impl<'a> TextDisplay<'a> {
             off_y: 0,
             cols,
             rows,
-            display,
+            displays,
         }
     }
 
@@ -122,26 +122,19 @@ impl<'a> TextDisplay<'a> {
         //let bg = Color::rgb(0, 0, 0);
         //self.display.rect(self.off_x, self.off_y, self.cols * 8, self.rows * 16, bg);
         //self.display.blit(0, self.off_y, w, self.rows * 16);
-        self.display.sync();
+        for display in self.displays.iter_mut() {
+            display.sync();
+        }
     }
 
     pub fn scroll(&mut self, color: Color) {
         if self.rows > 0 {
-            let w = self.display.width();
-
-            let dst = self.off_y * w as i32;
-            let src = (self.off_y + 16) * w as i32;
-            let len = (self.rows - 1) * 16 * w as usize;
-            unsafe {
-                let scale = self.display.scale() as isize;
-                let data_ptr = self.display.data_mut().as_mut_ptr() as *mut u32;
-                crate::display::fast_copy(
-                    data_ptr.offset(dst as isize * scale * scale) as *mut u8,
-                    data_ptr.offset(src as isize * scale * scale) as *const u8,
-                    len * (scale * scale) as usize * 4);
-            }
+            for display in self.displays.iter_mut() {
+                // Rows start at the top, so the whole display can be scrolled
+                display.scroll(16, color);
 
-            self.display.rect(self.off_x, self.off_y + (self.rows as i32 - 1) * 16, self.cols as u32 * 8, 16, color);
+                display.rect(self.off_x, self.off_y + (self.rows as i32 - 1) * 16, self.cols as u32 * 8, 16, color);
+            }
         }
     }
 
@@ -180,7 +173,9 @@ impl<'a> TextDisplay<'a> {
             match c {
                 '\x08' => if self.mode.CursorColumn > 0 {
                     let (x, y) = self.pos();
-                    self.display.rect(x, y, 8, 16, bg);
+                    for display in self.displays.iter_mut() {
+                        display.rect(x, y, 8, 16, bg);
+                    }
                     self.mode.CursorColumn -= 1;
                     changed = true;
                 },
@@ -192,8 +187,10 @@ impl<'a> TextDisplay<'a> {
                 },
                 _ => {
                     let (x, y) = self.pos();
-                    self.display.rect(x, y, 8, 16, bg);
-                    self.display.char(x, y, c, fg);
+                    for display in self.displays.iter_mut() {
+                        display.rect(x, y, 8, 16, bg);
+                        display.char(x, y, c, fg);
+                    }
                     self.mode.CursorColumn += 1;
                     changed = true;
                 }
@@ -202,15 +199,20 @@ impl<'a> TextDisplay<'a> {
             i += 1;
         }
 
-        if scrolled {
-            let (cx, cw) = (0, self.display.width() as i32);
-            let (cy, ch) = (self.off_y, self.rows as u32 * 16);
-            self.display.blit(cx, cy, cw as u32, ch as u32);
+        let blit_opt = if scrolled {
+            Some((self.off_y, self.rows as i32 * 16))
         } else if changed {
             let (_x, y) = self.pos();
-            let (cx, cw) = (0, self.display.width() as i32);
-            let (cy, ch) = (sy, y + 16 - sy);
-            self.display.blit(cx, cy, cw as u32, ch as u32);
+            Some((sy, y + 16 - sy))
+        } else {
+            None
+        };
+
+        if let Some((cy, ch)) = blit_opt {
+            for display in self.displays.iter_mut() {
+                let (cx, cw) = (0, display.width() as i32);
+                display.blit(cx, cy, cw as u32, ch as u32);
+            }
         }
     }
 
@@ -245,6 +247,9 @@ impl<'a> TextDisplay<'a> {
 }
 
 pub fn pipe<T, F: FnMut() -> Result<T>>(f: F) -> Result<T> {
-    let mut display = Display::new(Output::one()?);
-    TextDisplay::new(ScaledDisplay::new(&mut display)).pipe(f)
+    let mut displays: Vec<Display> = Output::physical().into_iter().map(Display::new).collect();
+    if displays.is_empty() {
+        return Err(Error::NotFound);
+    }
+    TextDisplay::new(displays.iter_mut().map(ScaledDisplay::new).collect()).pipe(f)
 }
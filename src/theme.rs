@@ -0,0 +1,192 @@
+use orbclient::Color;
+
+use crate::fs;
+
+/// Path of the theme file on the ESP, read when the first form is shown
+pub const THEME_PATH: &str = "\\firmware-setup\\theme.txt";
+
+/// Colors, spacing and font sizes of the form display. Sizes are in pixels at a scale of 1
+#[derive(Clone)]
+pub struct Theme {
+    pub background_color: Color,
+    pub highlight_color: Color,
+    pub outline_color: Color,
+    pub text_color: Color,
+    pub grayout_color: Color,
+    pub scrollbar_color: Color,
+    pub highlight_text_color: Color,
+    pub padding_lr: i32,
+    pub padding_tb: i32,
+    pub margin_lr: i32,
+    pub margin_tb: i32,
+    pub indent: i32,
+    pub scrollbar_w: i32,
+    pub title_font_size: i32,
+    pub font_size: i32,
+    pub help_font_size: i32,
+}
+
+impl Theme {
+    /// The default theme, light text on a dark background
+    pub fn dark() -> Self {
+        Self {
+            background_color: Color::rgb(0x36, 0x32, 0x2F),
+            highlight_color: Color::rgb(0xFB, 0xB8, 0x6C),
+            outline_color: Color::rgba(0xfe, 0xff, 0xff, 0xc4),
+            text_color: Color::rgb(0xCC, 0xCC, 0xCC),
+            grayout_color: Color::rgba(0x36, 0x32, 0x2F, 0xa0),
+            scrollbar_color: Color::rgba(0xfe, 0xff, 0xff, 0x30),
+            highlight_text_color: Color::rgb(0x27, 0x27, 0x27),
+            padding_lr: 4,
+            padding_tb: 2,
+            margin_lr: 8,
+            margin_tb: 4,
+            indent: 16,
+            scrollbar_w: 4,
+            title_font_size: 20,
+            font_size: 16,
+            help_font_size: 12,
+        }
+    }
+
+    /// Dark text on a light background
+    pub fn light() -> Self {
+        Self {
+            background_color: Color::rgb(0xF5, 0xF5, 0xF5),
+            highlight_color: Color::rgb(0xFB, 0xB8, 0x6C),
+            outline_color: Color::rgba(0x27, 0x27, 0x27, 0xc4),
+            text_color: Color::rgb(0x33, 0x33, 0x33),
+            grayout_color: Color::rgba(0xF5, 0xF5, 0xF5, 0xa0),
+            scrollbar_color: Color::rgba(0x27, 0x27, 0x27, 0x30),
+            highlight_text_color: Color::rgb(0x27, 0x27, 0x27),
+            ..Self::dark()
+        }
+    }
+
+    /// Copy of the theme with a high contrast palette, keeping its spacing and font sizes
+    pub fn high_contrast(&self) -> Self {
+        Self {
+            background_color: Color::rgb(0x00, 0x00, 0x00),
+            highlight_color: Color::rgb(0xFF, 0xFF, 0x00),
+            outline_color: Color::rgb(0xFF, 0xFF, 0xFF),
+            text_color: Color::rgb(0xFF, 0xFF, 0xFF),
+            grayout_color: Color::rgba(0x00, 0x00, 0x00, 0x80),
+            scrollbar_color: Color::rgba(0xFF, 0xFF, 0xFF, 0x80),
+            highlight_text_color: Color::rgb(0x00, 0x00, 0x00),
+            ..self.clone()
+        }
+    }
+
+    /// Find a built-in theme by name
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::dark().high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Parse a theme file. Each line is a `key = value` pair, and lines starting with `#` are
+    /// ignored. A `preset` key selects the built-in theme that the remaining keys modify, and
+    /// must come first. Colors are written as `#RRGGBB` or `#RRGGBBAA`
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut theme = Self::dark();
+        // A preset after other keys would silently replace them
+        let mut modified = false;
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(format!("line {}: expected key = value", i + 1)),
+            };
+
+            let color = || parse_color(value).ok_or_else(|| {
+                format!("line {}: invalid color '{}'", i + 1, value)
+            });
+            let size = || match value.parse::<i32>() {
+                Ok(size) if size >= 0 => Ok(size),
+                _ => Err(format!("line {}: invalid size '{}'", i + 1, value)),
+            };
+            // Text cannot be rendered at a size of zero, and rows of it would have no height
+            let font_size = || match value.parse::<i32>() {
+                Ok(size) if size > 0 => Ok(size),
+                _ => Err(format!("line {}: invalid font size '{}'", i + 1, value)),
+            };
+
+            if key == "preset" && modified {
+                return Err(format!("line {}: preset must come before other keys", i + 1));
+            }
+            modified = true;
+
+            match key {
+                "preset" => theme = Self::preset(value).ok_or_else(|| {
+                    format!("line {}: unknown preset '{}'", i + 1, value)
+                })?,
+                "background_color" => theme.background_color = color()?,
+                "highlight_color" => theme.highlight_color = color()?,
+                "outline_color" => theme.outline_color = color()?,
+                "text_color" => theme.text_color = color()?,
+                "grayout_color" => theme.grayout_color = color()?,
+                "scrollbar_color" => theme.scrollbar_color = color()?,
+                "highlight_text_color" => theme.highlight_text_color = color()?,
+                "padding_lr" => theme.padding_lr = size()?,
+                "padding_tb" => theme.padding_tb = size()?,
+                "margin_lr" => theme.margin_lr = size()?,
+                "margin_tb" => theme.margin_tb = size()?,
+                "indent" => theme.indent = size()?,
+                "scrollbar_w" => theme.scrollbar_w = size()?,
+                "title_font_size" => theme.title_font_size = font_size()?,
+                "font_size" => theme.font_size = font_size()?,
+                "help_font_size" => theme.help_font_size = font_size()?,
+                _ => return Err(format!("line {}: unknown key '{}'", i + 1, key)),
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Load the theme file from any volume, using the default theme if it is missing or invalid
+    pub fn load() -> Self {
+        let data = match fs::read(THEME_PATH) {
+            Ok(ok) => ok,
+            Err(err) => {
+                debugln!("theme: failed to read {}: {:?}", THEME_PATH, err);
+                return Self::dark();
+            }
+        };
+
+        let source = match String::from_utf8(data) {
+            Ok(ok) => ok,
+            Err(err) => {
+                debugln!("theme: {} is not UTF-8: {}", THEME_PATH, err);
+                return Self::dark();
+            }
+        };
+
+        match Self::parse(&source) {
+            Ok(ok) => ok,
+            Err(err) => {
+                debugln!("theme: failed to parse {}: {}", THEME_PATH, err);
+                Self::dark()
+            }
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix('#')?;
+    let byte = |i: usize| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok());
+    match hex.len() {
+        6 => Some(Color::rgb(byte(0)?, byte(2)?, byte(4)?)),
+        8 => Some(Color::rgba(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+        _ => None,
+    }
+}
@@ -0,0 +1,45 @@
+use core::ptr;
+use std::ops::Try;
+use uefi::guid::Guid;
+use uefi::status::Result;
+
+/// Vendor GUID for the settings stored by this application
+pub const SETUP_SETTINGS_GUID: Guid = Guid(0x3f5bf1a4, 0x8c5e, 0x4b6b, [0x9d, 0x2a, 0x61, 0xe0, 0x4c, 0x7b, 0x15, 0xd3]);
+
+// TODO: move to uefi library {
+pub const VARIABLE_NON_VOLATILE: u32 = 0x00000001;
+pub const VARIABLE_BOOTSERVICE_ACCESS: u32 = 0x00000002;
+// } TODO: move to uefi library
+
+/// Read a settings variable, up to the size of data. Returns the number of bytes read
+pub fn get(name: &str, data: &mut [u8]) -> Result<usize> {
+    let uefi = std::system_table();
+
+    let name_w: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+    let mut size = data.len();
+    (uefi.RuntimeServices.GetVariable)(
+        name_w.as_ptr(),
+        &SETUP_SETTINGS_GUID,
+        ptr::null_mut(),
+        &mut size,
+        data.as_mut_ptr()
+    ).into_result()?;
+
+    Ok(size)
+}
+
+/// Write a non-volatile settings variable, so it survives reboots
+pub fn set(name: &str, data: &[u8]) -> Result<()> {
+    let uefi = std::system_table();
+
+    let name_w: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+    (uefi.RuntimeServices.SetVariable)(
+        name_w.as_ptr(),
+        &SETUP_SETTINGS_GUID,
+        VARIABLE_NON_VOLATILE | VARIABLE_BOOTSERVICE_ACCESS,
+        data.len(),
+        data.as_ptr()
+    ).into_result()?;
+
+    Ok(())
+}